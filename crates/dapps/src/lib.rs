@@ -14,6 +14,10 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Ethcore Webapplications for Parity
+//!
+//! Not built as part of `toyrpc`: endpoints and handlers still follow the asynchronous
+//! hyper 0.10 API, while `jsonrpc-http-server` uses hyper 0.11, and the crate requires
+//! the npm-built `parity-ui`. `toyrpc` does not serve dapps until they are ported.
 #![warn(missing_docs)]
 #![cfg_attr(feature="nightly", feature(plugin))]
 #![cfg_attr(feature="nightly", plugin(clippy))]
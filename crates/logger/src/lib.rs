@@ -30,15 +30,17 @@ extern crate ansi_term;
 mod rotating;
 
 use std::{env, thread, fs};
+use std::str::FromStr;
 use std::sync::{Weak, Arc};
 use std::io::Write;
 use isatty::{stderr_isatty, stdout_isatty};
 use env_logger::LogBuilder;
 use regex::Regex;
 use ansi_term::Colour;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
+use rlog::{Log, LogRecord, LogMetadata, LogLevelFilter, MaxLogLevelFilter};
 
-pub use rotating::{RotatingLogger, init_log};
+pub use rotating::{RotatingLogger, LogEntry, init_log};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Config {
@@ -57,15 +59,36 @@ impl Default for Config {
 	}
 }
 
+type Format = Arc<Fn(&LogRecord) -> String + Sync + Send>;
+
+/// Handle used to replace filters of the installed logger.
+struct Filters {
+	logger: Arc<RwLock<env_logger::Logger>>,
+	format: Format,
+	max_level: MaxLogLevelFilter,
+}
+
+/// Installed logger, delegates to `env_logger` which can be rebuilt with different filters.
+struct Logger {
+	inner: Arc<RwLock<env_logger::Logger>>,
+}
+
+impl Log for Logger {
+	fn enabled(&self, metadata: &LogMetadata) -> bool {
+		self.inner.read().enabled(metadata)
+	}
+
+	fn log(&self, record: &LogRecord) {
+		self.inner.read().log(record)
+	}
+}
+
 lazy_static! {
 	static ref ROTATING_LOGGER : Mutex<Weak<RotatingLogger>> = Mutex::new(Default::default());
+	static ref FILTERS : Mutex<Option<Filters>> = Mutex::new(None);
 }
 
-/// Sets up the logger
-pub fn setup_log(config: &Config) -> Result<Arc<RotatingLogger>, String> {
-	use rlog::*;
-
-	let mut levels = String::new();
+fn build_logger(levels: &str, format: Format) -> env_logger::Logger {
 	let mut builder = LogBuilder::new();
 	// Disable ws info logging by default.
 	builder.filter(Some("ws"), LogLevelFilter::Warn);
@@ -73,20 +96,39 @@ pub fn setup_log(config: &Config) -> Result<Arc<RotatingLogger>, String> {
 	builder.filter(Some("rustls"), LogLevelFilter::Warn);
 	builder.filter(None, LogLevelFilter::Info);
 
+	if !levels.is_empty() {
+		builder.parse(levels);
+	}
+
+	builder.format(move |record: &LogRecord| format(record));
+	builder.build()
+}
+
+/// Returns log levels set by `RUST_LOG` followed by the configured mode.
+pub fn log_levels(config: &Config) -> String {
+	let mut levels = String::new();
+
 	if let Ok(lvl) = env::var("RUST_LOG") {
 		levels.push_str(&lvl);
 		levels.push_str(",");
-		builder.parse(&lvl);
 	}
 
 	if let Some(ref s) = config.mode {
 		levels.push_str(s);
-		builder.parse(s);
 	}
 
+	levels
+}
+
+/// Sets up the logger
+pub fn setup_log(config: &Config) -> Result<Arc<RotatingLogger>, String> {
+	use rlog::*;
+
+	let levels = log_levels(config);
+
 	let isatty = stderr_isatty();
 	let enable_color = config.color && isatty;
-	let logs = Arc::new(RotatingLogger::new(levels));
+	let logs = Arc::new(RotatingLogger::new(levels.clone()));
 	let logger = logs.clone();
 	let mut open_options = fs::OpenOptions::new();
 
@@ -119,7 +161,7 @@ pub fn setup_log(config: &Config) -> Result<Arc<RotatingLogger>, String> {
 			let _ = file.write_all(removed_color.as_bytes());
 			let _ = file.write_all(b"\n");
 		}
-		logger.append(removed_color);
+		logger.append(record.level(), record.target(), removed_color);
 		if !isatty && record.level() <= LogLevel::Info && stdout_isatty() {
 			// duplicate INFO/WARN output to console
 			println!("{}", ret);
@@ -128,8 +170,17 @@ pub fn setup_log(config: &Config) -> Result<Arc<RotatingLogger>, String> {
 		ret
     };
 
-	builder.format(format);
-	builder.init()
+	let format: Format = Arc::new(format);
+	let inner = Arc::new(RwLock::new(build_logger(&levels, format.clone())));
+	set_logger(|max_level| {
+			max_level.set(inner.read().filter());
+			*FILTERS.lock() = Some(Filters {
+				logger: inner.clone(),
+				format: format,
+				max_level: max_level,
+			});
+			Box::new(Logger { inner: inner })
+		})
 		.and_then(|_| {
 			*ROTATING_LOGGER.lock() = Arc::downgrade(&logs);
 			Ok(logs)
@@ -142,6 +193,42 @@ pub fn setup_log(config: &Config) -> Result<Arc<RotatingLogger>, String> {
 		})
 }
 
+/// Checks that `levels` is a valid filter in `RUST_LOG` format.
+pub fn validate_log_levels(levels: &str) -> Result<(), String> {
+	let directives = levels.splitn(2, '/').next().unwrap_or_default();
+	for directive in directives.split(',').filter(|directive| !directive.is_empty()) {
+		let mut parts = directive.splitn(2, '=');
+		let target = parts.next().unwrap_or_default();
+		match parts.next() {
+			Some(level) if target.is_empty() || LogLevelFilter::from_str(level).is_err() => {
+				return Err(format!("Invalid log directive: {}", directive));
+			}
+			_ => {}
+		}
+	}
+	Ok(())
+}
+
+/// Replaces filters of the running logger. `levels` uses the same format as `RUST_LOG`.
+pub fn set_log_levels(levels: &str) -> Result<(), String> {
+	validate_log_levels(levels)?;
+
+	let filters = FILTERS.lock();
+	let filters = match *filters {
+		Some(ref filters) => filters,
+		None => return Err("Logger is not initialized.".into()),
+	};
+
+	let logger = build_logger(levels, filters.format.clone());
+	filters.max_level.set(logger.filter());
+	*filters.logger.write() = logger;
+
+	if let Some(rotating) = ROTATING_LOGGER.lock().upgrade() {
+		rotating.set_levels(levels.into());
+	}
+	Ok(())
+}
+
 fn kill_color(s: &str) -> String {
 	lazy_static! {
 		static ref RE: Regex = Regex::new("\x1b\\[[^m]+m").unwrap();
@@ -156,6 +243,16 @@ fn should_remove_colour() {
 	assert_eq!(after, "test");
 }
 
+#[test]
+fn should_validate_log_levels() {
+	assert!(validate_log_levels("").is_ok());
+	assert!(validate_log_levels("info").is_ok());
+	assert!(validate_log_levels("rpc=trace,ws=warn").is_ok());
+	assert!(validate_log_levels("rpc=debug/foo").is_ok());
+	assert!(validate_log_levels("rpc=loud").is_err());
+	assert!(validate_log_levels("=info").is_err());
+}
+
 #[test]
 fn should_remove_multiple_colour() {
 	let t = format!("{} {}", Colour::Red.bold().paint("test"), Colour::White.normal().paint("again"));
@@ -17,7 +17,7 @@
 //! Common log helper functions
 
 use std::env;
-use rlog::LogLevelFilter;
+use rlog::{LogLevel, LogLevelFilter};
 use env_logger::LogBuilder;
 use arrayvec::ArrayVec;
 
@@ -45,12 +45,23 @@ pub fn init_log() {
 
 const LOG_SIZE : usize = 128;
 
+/// Single formatted log line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+	/// Level of the record
+	pub level: LogLevel,
+	/// Target of the record
+	pub target: String,
+	/// Formatted line
+	pub line: String,
+}
+
 /// Logger implementation that keeps up to `LOG_SIZE` log elements.
 pub struct RotatingLogger {
 	/// Defined logger levels
-	levels: String,
+	levels: RwLock<String>,
 	/// Logs array. Latest log is always at index 0
-	logs: RwLock<ArrayVec<[String; LOG_SIZE]>>,
+	logs: RwLock<ArrayVec<[LogEntry; LOG_SIZE]>>,
 }
 
 impl RotatingLogger {
@@ -59,23 +70,32 @@ impl RotatingLogger {
 	/// It does not enforce levels - it's just read only.
 	pub fn new(levels: String) -> Self {
 		RotatingLogger {
-			levels: levels,
+			levels: RwLock::new(levels),
 			logs: RwLock::new(ArrayVec::<[_; LOG_SIZE]>::new()),
 		}
 	}
 
 	/// Append new log entry
-	pub fn append(&self, log: String) {
-		self.logs.write().insert(0, log);
+	pub fn append(&self, level: LogLevel, target: &str, log: String) {
+		self.logs.write().insert(0, LogEntry {
+			level: level,
+			target: target.into(),
+			line: log,
+		});
 	}
 
 	/// Return levels
-	pub fn levels(&self) -> &str {
-		&self.levels
+	pub fn levels(&self) -> String {
+		self.levels.read().clone()
+	}
+
+	/// Update levels after the logger filter has changed
+	pub fn set_levels(&self, levels: String) {
+		*self.levels.write() = levels;
 	}
 
 	/// Return logs
-	pub fn logs(&self) -> RwLockReadGuard<ArrayVec<[String; LOG_SIZE]>> {
+	pub fn logs(&self) -> RwLockReadGuard<ArrayVec<[LogEntry; LOG_SIZE]>> {
 		self.logs.read()
 	}
 
@@ -83,6 +103,7 @@ impl RotatingLogger {
 
 #[cfg(test)]
 mod test {
+	use rlog::LogLevel;
 	use super::RotatingLogger;
 
 	fn logger() -> RotatingLogger {
@@ -101,19 +122,33 @@ mod test {
 		assert_eq!(levels, "test");
 	}
 
+	#[test]
+	fn should_update_log_levels() {
+		// given
+		let logger = logger();
+
+		// when
+		logger.set_levels("rpc=trace".to_owned());
+
+		// then
+		assert_eq!(logger.levels(), "rpc=trace");
+	}
+
 	#[test]
 	fn should_return_latest_logs() {
 		// given
 		let logger = logger();
 
 		// when
-		logger.append("a".to_owned());
-		logger.append("b".to_owned());
+		logger.append(LogLevel::Info, "rpc", "a".to_owned());
+		logger.append(LogLevel::Warn, "ws", "b".to_owned());
 
 		// then
 		let logs = logger.logs();
-		assert_eq!(logs[0], "b".to_owned());
-		assert_eq!(logs[1], "a".to_owned());
+		assert_eq!(logs[0].line, "b".to_owned());
+		assert_eq!(logs[0].level, LogLevel::Warn);
+		assert_eq!(logs[0].target, "ws".to_owned());
+		assert_eq!(logs[1].line, "a".to_owned());
 		assert_eq!(logs.len(), 2);
 	}
 }
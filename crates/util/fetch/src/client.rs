@@ -126,6 +126,42 @@ impl Client {
 		*self.client.write() = (time::Instant::now(), client.clone());
 		Ok(client)
 	}
+
+	/// POST JSON `body` to given URL and get a future for the result.
+	/// Supports aborting the request in the middle of execution.
+	pub fn post_json_with_abort(&self, url: &str, body: Vec<u8>, abort: Abort) -> CpuFuture<Response, Error> {
+		self.post(url, body, None, abort)
+	}
+
+	/// POST JSON `body` to given URL and get a future for the result.
+	pub fn post_json(&self, url: &str, body: Vec<u8>) -> CpuFuture<Response, Error> {
+		self.post_json_with_abort(url, body, Default::default())
+	}
+
+	/// POST JSON `body` to given URL, authorizing with a bearer token if given.
+	pub fn post_json_with_token(&self, url: &str, body: Vec<u8>, token: Option<String>) -> CpuFuture<Response, Error> {
+		self.post(url, body, token, Default::default())
+	}
+
+	fn post(&self, url: &str, body: Vec<u8>, token: Option<String>, abort: Abort) -> CpuFuture<Response, Error> {
+		debug!(target: "fetch", "Posting to: {:?}", url);
+
+		match self.client() {
+			Ok(client) => {
+				self.pool.spawn(FetchTask {
+					url: url.into(),
+					body: Some(body),
+					token: token,
+					client: client,
+					limit: self.limit,
+					abort: abort,
+				})
+			},
+			Err(err) => {
+				self.pool.spawn(futures::future::err(err))
+			},
+		}
+	}
 }
 
 impl Fetch for Client {
@@ -151,6 +187,8 @@ impl Fetch for Client {
 			Ok(client) => {
 				self.pool.spawn(FetchTask {
 					url: url.into(),
+					body: None,
+					token: None,
 					client: client,
 					limit: self.limit,
 					abort: abort,
@@ -165,6 +203,8 @@ impl Fetch for Client {
 
 struct FetchTask {
 	url: String,
+	body: Option<Vec<u8>>,
+	token: Option<String>,
 	client: Arc<reqwest::Client>,
 	limit: Option<usize>,
 	abort: Abort,
@@ -182,7 +222,17 @@ impl Future for FetchTask {
 		}
 
 		trace!(target: "fetch", "Starting fetch task: {:?}", self.url);
-		let result = self.client.get(&self.url)
+		let request = match self.body.take() {
+			Some(body) => self.client.post(&self.url)
+				.header(reqwest::header::ContentType::json())
+				.body(body),
+			None => self.client.get(&self.url),
+		};
+		let request = match self.token.take() {
+			Some(token) => request.header(reqwest::header::Authorization(reqwest::header::Bearer { token: token })),
+			None => request,
+		};
+		let result = request
 						  .header(reqwest::header::UserAgent("Parity Fetch".into()))
 						  .send()?;
 
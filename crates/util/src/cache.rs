@@ -52,6 +52,8 @@ impl<K: Eq + Hash, V: HeapSizeOf> MemoryLruCache<K, V> {
 			self.inner.set_capacity(cap * 2);
 		}
 
+		self.cur_size += val.heap_size_of_children();
+
 		// account for any element displaced from the cache.
 		if let Some(lru) = self.inner.insert(key, val) {
 			self.cur_size -= lru.heap_size_of_children();
@@ -76,4 +78,29 @@ impl<K: Eq + Hash, V: HeapSizeOf> MemoryLruCache<K, V> {
 	pub fn current_size(&self) -> usize {
 		self.cur_size
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use heapsize::HeapSizeOf;
+	use super::MemoryLruCache;
+
+	#[test]
+	fn it_works() {
+		let mut cache = MemoryLruCache::new(256);
+		let val1 = vec![0u8; 100];
+		let size1 = val1.heap_size_of_children();
+		cache.insert("hello", val1);
+
+		assert_eq!(cache.current_size(), size1);
+
+		let val2 = vec![0u8; 210];
+		let size2 = val2.heap_size_of_children();
+		cache.insert("world", val2);
+
+		assert!(cache.get_mut(&"hello").is_none());
+		assert!(cache.get_mut(&"world").is_some());
+
+		assert_eq!(cache.current_size(), size2);
+	}
+}
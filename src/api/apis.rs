@@ -15,14 +15,19 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::cmp::PartialEq;
-use std::collections::BTreeMap;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use jsonrpc_core::MetaIoHandler;
+use jsonrpc_core::{MetaIoHandler, RemoteProcedure};
 use ethcore_logger::RotatingLogger;
-use util::informant::{Middleware, RpcStats, ClientNotifier, CpuPool};
+use parking_lot::RwLock;
+use api::discovery::{self, MethodDoc};
+use util::informant::Middleware;
+use util::response_cache::{self, CacheableMethods, ResponseCache};
+use util::subscription_manager::SubscriptionManager;
 use types::Metadata;
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -47,6 +52,10 @@ pub enum Api {
     Traces,
     /// Rpc (Safe)
     Rpc,
+    /// PubSub - server-push notifications (Safe)
+    PubSub,
+    /// Module registered by the embedding application
+    Custom(String),
 }
 
 impl FromStr for Api {
@@ -66,12 +75,148 @@ impl FromStr for Api {
             "parity_set" => Ok(ParitySet),
             "traces" => Ok(Traces),
             "rpc" => Ok(Rpc),
+            "pubsub" => Ok(PubSub),
             api => Err(format!("Unknown api: {}", api)),
         }
     }
 }
 
-#[derive(Debug)]
+impl Api {
+    /// Parses name of a built-in API or of one of given modules.
+    pub fn parse_with(s: &str, modules: &[Module]) -> Result<Self, String> {
+        s.parse().or_else(|err| modules.iter().find(|module| module.name == s).map(Module::api).ok_or(err))
+    }
+
+    /// Returns safety class of the API. Custom APIs not backed by any of given modules are unsafe.
+    pub fn safety(&self, modules: &[Module]) -> ApiSafety {
+        match *self {
+            Api::Custom(_) => modules.iter().find(|module| module.api() == *self).map_or(ApiSafety::Unsafe, Module::safety),
+            ref api if ApiSet::UnsafeContext.list_apis().contains(api) => ApiSafety::Safe,
+            ref api if ApiSet::IpcContext.list_apis().contains(api) => ApiSafety::SemiSafe,
+            _ => ApiSafety::Unsafe,
+        }
+    }
+
+    /// Returns name of the module.
+    pub fn name(&self) -> &str {
+        match *self {
+            Api::Web3 => "web3",
+            Api::Net => "net",
+            Api::Eth => "eth",
+            Api::Personal => "personal",
+            Api::Signer => "signer",
+            Api::Parity => "parity",
+            Api::ParityAccounts => "parity_accounts",
+            Api::ParitySet => "parity_set",
+            Api::Traces => "traces",
+            Api::Rpc => "rpc",
+            Api::PubSub => "pubsub",
+            Api::Custom(ref name) => name,
+        }
+    }
+}
+
+impl fmt::Display for Api {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Decides which API sets include a module.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ApiSafety {
+    /// Available everywhere
+    Safe,
+    /// Available over IPC and in safe contexts
+    SemiSafe,
+    /// Available only in safe contexts
+    Unsafe,
+}
+
+impl ApiSafety {
+    /// Returns name of the safety class.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ApiSafety::Safe => "safe",
+            ApiSafety::SemiSafe => "semi-safe",
+            ApiSafety::Unsafe => "unsafe",
+        }
+    }
+}
+
+/// Module of methods registered in addition to the built-in APIs.
+#[derive(Clone)]
+pub struct Module {
+    name: String,
+    version: String,
+    safety: ApiSafety,
+    methods: HashMap<String, RemoteProcedure<Metadata>>,
+    docs: HashMap<String, MethodDoc>,
+    cacheable: CacheableMethods,
+}
+
+impl Module {
+    /// Creates new module from a delegate, e.g. one returned by `to_delegate` of a `build_rpc_trait!` trait.
+    pub fn new<N, V, D>(name: N, version: V, safety: ApiSafety, delegate: D) -> Self
+        where N: Into<String>,
+              V: Into<String>,
+              D: Into<HashMap<String, RemoteProcedure<Metadata>>>
+    {
+        Module {
+            name: name.into(),
+            version: version.into(),
+            safety: safety,
+            methods: delegate.into(),
+            docs: HashMap::new(),
+            cacheable: HashMap::new(),
+        }
+    }
+
+    /// Describes parameters and result of a method for `rpc_discover`.
+    pub fn describe<M: Into<String>>(mut self, method: M, doc: MethodDoc) -> Self {
+        self.docs.insert(method.into(), doc);
+        self
+    }
+
+    /// Declares results of a method as cacheable for given time.
+    /// Only methods whose results do not depend on the caller should be cached.
+    pub fn cache<M: Into<String>>(mut self, method: M, ttl: Duration) -> Self {
+        self.cacheable.insert(method.into(), ttl);
+        self
+    }
+
+    /// Returns methods declared as cacheable.
+    pub fn cacheable(&self) -> &CacheableMethods {
+        &self.cacheable
+    }
+
+    /// Returns descriptions of methods.
+    pub fn docs(&self) -> &HashMap<String, MethodDoc> {
+        &self.docs
+    }
+
+    /// Returns name of the module.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns version of the module.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Returns safety class of the module.
+    pub fn safety(&self) -> ApiSafety {
+        self.safety
+    }
+
+    /// Returns `Api` identifying this module.
+    pub fn api(&self) -> Api {
+        Api::Custom(self.name.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum ApiSet {
     SafeContext,
     UnsafeContext,
@@ -102,33 +247,60 @@ impl FromStr for ApiSet {
     }
 }
 
+impl ApiSet {
+    /// Parses comma-separated list of built-in APIs and names of given modules.
+    pub fn parse_with(s: &str, modules: &[Module]) -> Result<Self, String> {
+        s.split(',')
+            .map(|api| Api::parse_with(api, modules))
+            .collect::<Result<_, _>>()
+            .map(ApiSet::List)
+    }
+}
+
+/// Checks that names of modules are unique and do not clash with built-in APIs.
+pub fn validate_modules(modules: &[Module]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for module in modules {
+        if module.name.parse::<Api>().is_ok() {
+            return Err(format!("Module {} clashes with a built-in API.", module.name));
+        }
+        if !names.insert(module.name.as_str()) {
+            return Err(format!("Module {} is registered more than once.", module.name));
+        }
+    }
+    Ok(())
+}
+
+/// Maps method names to the module they belong to.
+pub type MethodApis = HashMap<String, Api>;
+
 pub struct Dependencies {
     pub logger: Arc<RotatingLogger>,
+    pub subscriptions: Arc<SubscriptionManager>,
+    /// Network id reported by `net_version`
+    pub network_id: u64,
+    /// Whether any of the RPC servers is enabled
+    pub listening: bool,
+    /// Modules registered by the embedding application
+    pub modules: Vec<Module>,
+    /// Cache of responses to cacheable methods
+    pub cache: Option<Arc<ResponseCache>>,
 }
 
-fn to_modules(apis: &[Api]) -> BTreeMap<String, String> {
+fn to_modules(apis: &[Api], custom: &[Module]) -> BTreeMap<String, String> {
     let mut modules = BTreeMap::new();
     for api in apis {
-        let (name, version) = match *api {
-            Api::Web3 => ("web3", "1.0"),
-            Api::Net => ("net", "1.0"),
-            Api::Eth => ("eth", "1.0"),
-            Api::Personal => ("personal", "1.0"),
-            Api::Signer => ("signer", "1.0"),
-            Api::Parity => ("parity", "1.0"),
-            Api::ParityAccounts => ("parity_accounts", "1.0"),
-            Api::ParitySet => ("parity_set", "1.0"),
-            Api::Traces => ("traces", "1.0"),
-            Api::Rpc => ("rpc", "1.0"),
-        };
-        modules.insert(name.into(), version.into());
+        let version = custom.iter()
+            .find(|module| module.api() == *api)
+            .map_or("1.0", |module| module.version());
+        modules.insert(api.name().into(), version.into());
     }
     modules
 }
 
 impl ApiSet {
     pub fn list_apis(&self) -> HashSet<Api> {
-        let mut safe_list = vec![Api::Web3, Api::Net, Api::Eth, Api::Parity, Api::Traces, Api::Rpc]
+        let mut safe_list = vec![Api::Web3, Api::Net, Api::Eth, Api::Parity, Api::Traces, Api::Rpc, Api::PubSub]
             .into_iter()
             .collect();
         match *self {
@@ -146,44 +318,108 @@ impl ApiSet {
             }
         }
     }
+
+    /// Returns built-in APIs and custom modules included in this set.
+    pub fn list_apis_with(&self, modules: &[Module]) -> HashSet<Api> {
+        let mut apis = self.list_apis();
+        apis.extend(modules.iter().filter(|module| self.includes(module)).map(Module::api));
+        apis
+    }
+
+    fn includes(&self, module: &Module) -> bool {
+        match *self {
+            ApiSet::List(ref apis) => apis.contains(&module.api()),
+            ApiSet::UnsafeContext => module.safety == ApiSafety::Safe,
+            ApiSet::IpcContext => module.safety != ApiSafety::Unsafe,
+            ApiSet::SafeContext => true,
+        }
+    }
 }
 
-pub fn setup_apis(stats: Arc<RpcStats>,
+pub fn setup_apis(middleware: Middleware,
                  deps: Arc<Dependencies>,
-                 apis: ApiSet,
-                 pool: Option<CpuPool>)
+                 apis: ApiSet)
                  -> MetaIoHandler<Metadata, Middleware> {
     use traits::*;
     use impls::*;
 
-    let mut handler = MetaIoHandler::with_middleware(Middleware::new(stats, ClientNotifier {}, pool));
-
     // it's turned into vector, cause ont of the cases requires &[]
-    let apis = apis.list_apis().into_iter().collect::<Vec<_>>();
-
-    for api in &apis {
-        match *api {
-            Api::Web3 => {}
-            Api::Net => {}
-            Api::Eth => {}
-            Api::Personal => {}
-            Api::Signer => {}
-            Api::Parity => {}
-            Api::ParityAccounts => {}
-            Api::ParitySet => {}
-            Api::Traces => {}
-            Api::Rpc => {
-                let modules = to_modules(&apis);
-                handler.extend_with(RpcClient::new(modules).to_delegate());
+    let apis = apis.list_apis_with(&deps.modules).into_iter().collect::<Vec<_>>();
+
+    let mut methods = MethodApis::new();
+    let mut cacheable = CacheableMethods::new();
+    let mut discover_document = None;
+    let mut delegates: Vec<HashMap<String, RemoteProcedure<Metadata>>> = Vec::new();
+    {
+        let mut add = |api: &Api, delegate: HashMap<String, RemoteProcedure<Metadata>>| {
+            methods.extend(delegate.keys().map(|method| (method.clone(), api.clone())));
+            delegates.push(delegate);
+        };
+
+        for api in &apis {
+            match *api {
+                Api::Web3 => {
+                    add(api, Web3Client::new().to_delegate().into());
+                }
+                Api::Net => {
+                    add(api, NetClient::new(deps.network_id, deps.listening).to_delegate().into());
+                }
+                Api::Eth => {}
+                Api::Personal => {}
+                Api::Signer => {}
+                Api::Parity => {
+                    add(api, ParityClient::new(deps.logger.clone()).to_delegate().into());
+                }
+                Api::ParityAccounts => {}
+                Api::ParitySet => {
+                    add(api, ParitySetClient::new(deps.cache.clone()).to_delegate().into());
+                }
+                Api::Traces => {}
+                Api::Rpc => {
+                    let modules = to_modules(&apis, &deps.modules);
+                    // filled in once all methods are registered
+                    let document = Arc::new(RwLock::new(Default::default()));
+                    discover_document = Some(document.clone());
+                    add(api, RpcClient::new(modules, document).to_delegate().into());
+                }
+                Api::PubSub => {
+                    add(api, PubSubClient::new(deps.subscriptions.clone()).into_delegate().into());
+                }
+                Api::Custom(_) => {
+                    if let Some(module) = deps.modules.iter().find(|module| module.api() == *api) {
+                        cacheable.extend(module.cacheable.iter().map(|(method, ttl)| (method.clone(), *ttl)));
+                        add(api, module.methods.clone());
+                    }
+                }
             }
         }
     }
+
+    if let Some(document) = discover_document {
+        *document.write() = discovery::document(&methods, &deps.modules);
+    }
+
+    cacheable.extend(methods.keys().filter_map(|method| response_cache::builtin(method).map(|ttl| (method.clone(), ttl))));
+
+    let safe_apis = apis.iter().filter(|api| api.safety(&deps.modules) == ApiSafety::Safe).cloned().collect();
+    let middleware = middleware.with_cache(deps.cache.clone(), cacheable)
+        .with_method_apis(methods)
+        .with_safe_apis(safe_apis);
+    let mut handler = MetaIoHandler::with_middleware(middleware);
+    for delegate in delegates {
+        handler.extend_with(delegate);
+    }
     handler
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Api, ApiSet};
+    use std::collections::HashMap;
+    use super::{Api, ApiSet, ApiSafety, Module, validate_modules};
+
+    fn module(name: &str, safety: ApiSafety) -> Module {
+        Module::new(name, "2.0", safety, HashMap::new())
+    }
 
     #[test]
     fn test_api_parsing() {
@@ -197,6 +433,7 @@ mod test {
         assert_eq!(Api::ParitySet, "parity_set".parse().unwrap());
         assert_eq!(Api::Traces, "traces".parse().unwrap());
         assert_eq!(Api::Rpc, "rpc".parse().unwrap());
+        assert_eq!(Api::PubSub, "pubsub".parse().unwrap());
         assert!("rp".parse::<Api>().is_err());
     }
 
@@ -219,7 +456,8 @@ mod test {
                             Api::Eth,
                             Api::Parity,
                             Api::Traces,
-                            Api::Rpc]
+                            Api::Rpc,
+                            Api::PubSub]
             .into_iter()
             .collect();
         assert_eq!(ApiSet::UnsafeContext.list_apis(), expected);
@@ -234,6 +472,7 @@ mod test {
                             Api::Parity,
                             Api::Traces,
                             Api::Rpc,
+                            Api::PubSub,
                             // semi-safe
                             Api::ParityAccounts]
             .into_iter()
@@ -245,7 +484,7 @@ mod test {
     fn test_api_set_safe_context() {
         let expected = vec![
 			// safe
-			Api::Web3, Api::Net, Api::Eth, Api::Parity, Api::Traces, Api::Rpc,
+			Api::Web3, Api::Net, Api::Eth, Api::Parity, Api::Traces, Api::Rpc, Api::PubSub,
 			// semi-safe
 			Api::ParityAccounts,
 			// Unsafe
@@ -255,4 +494,58 @@ mod test {
             .collect();
         assert_eq!(ApiSet::SafeContext.list_apis(), expected);
     }
+
+    #[test]
+    fn test_api_set_with_custom_modules() {
+        let modules = vec![module("safe", ApiSafety::Safe),
+                           module("semi", ApiSafety::SemiSafe),
+                           module("unsafe", ApiSafety::Unsafe)];
+        let custom = |set: &ApiSet| {
+            let mut names = set.list_apis_with(&modules)
+                .into_iter()
+                .filter_map(|api| match api {
+                    Api::Custom(name) => Some(name),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(custom(&ApiSet::UnsafeContext), vec!["safe"]);
+        assert_eq!(custom(&ApiSet::IpcContext), vec!["safe", "semi"]);
+        assert_eq!(custom(&ApiSet::SafeContext), vec!["safe", "semi", "unsafe"]);
+        assert_eq!(custom(&ApiSet::List(vec![Api::Web3, Api::Custom("unsafe".into())].into_iter().collect())),
+                   vec!["unsafe"]);
+    }
+
+    #[test]
+    fn test_api_parsing_with_custom_modules() {
+        let modules = vec![module("hello", ApiSafety::Safe)];
+
+        assert_eq!(Api::Custom("hello".into()), Api::parse_with("hello", &modules).unwrap());
+        assert_eq!(Api::Web3, Api::parse_with("web3", &modules).unwrap());
+        assert!(Api::parse_with("hell", &modules).is_err());
+        assert_eq!(ApiSet::List(vec![Api::Web3, Api::Custom("hello".into())].into_iter().collect()),
+                   ApiSet::parse_with("web3,hello", &modules).unwrap());
+    }
+
+    #[test]
+    fn test_validate_modules() {
+        assert!(validate_modules(&[module("hello", ApiSafety::Safe), module("world", ApiSafety::Safe)]).is_ok());
+        assert!(validate_modules(&[module("web3", ApiSafety::Safe)]).is_err());
+        assert!(validate_modules(&[module("hello", ApiSafety::Safe), module("hello", ApiSafety::Unsafe)]).is_err());
+    }
+
+    #[test]
+    fn test_api_safety() {
+        let modules = vec![module("semi", ApiSafety::SemiSafe)];
+
+        assert_eq!(Api::Web3.safety(&modules), ApiSafety::Safe);
+        assert_eq!(Api::ParityAccounts.safety(&modules), ApiSafety::SemiSafe);
+        assert_eq!(Api::ParitySet.safety(&modules), ApiSafety::Unsafe);
+        assert_eq!(Api::Personal.safety(&modules), ApiSafety::Unsafe);
+        assert_eq!(Api::Custom("semi".into()).safety(&modules), ApiSafety::SemiSafe);
+        assert_eq!(Api::Custom("other".into()).safety(&modules), ApiSafety::Unsafe);
+    }
 }
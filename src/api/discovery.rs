@@ -0,0 +1,187 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Description of registered methods served by `rpc_discover`.
+
+use std::collections::HashMap;
+use types::{ContentDescriptor, DiscoverDocument, DiscoverInfo, MethodDescription};
+use util::misc::version;
+use super::apis::{MethodApis, Module};
+
+/// OpenRPC version the generated document follows.
+const OPENRPC_VERSION: &'static str = "1.0.0";
+
+/// Parameters and result of a method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodDoc {
+    /// Parameters in positional order
+    pub params: Vec<ContentDescriptor>,
+    /// Method result
+    pub result: ContentDescriptor,
+}
+
+impl MethodDoc {
+    /// Creates new `MethodDoc`.
+    pub fn new(params: Vec<ContentDescriptor>, result: ContentDescriptor) -> Self {
+        MethodDoc {
+            params: params,
+            result: result,
+        }
+    }
+}
+
+/// Describes methods of built-in APIs. Every method registered by `setup_apis` needs an entry.
+fn builtin(method: &str) -> Option<MethodDoc> {
+    let string = |name: &str| ContentDescriptor::new(name, "string");
+    let boolean = |name: &str| ContentDescriptor::new(name, "boolean");
+    let object = |name: &str| ContentDescriptor::new(name, "object");
+
+    let doc = match method {
+        "web3_clientVersion" => MethodDoc::new(vec![], string("version")),
+        "web3_sha3" => MethodDoc::new(vec![string("data")], string("hash")),
+        "net_version" => MethodDoc::new(vec![], string("networkId")),
+        "net_peerCount" => MethodDoc::new(vec![], string("peerCount")),
+        "net_listening" => MethodDoc::new(vec![], boolean("listening")),
+        "modules" | "rpc_modules" => MethodDoc::new(vec![], object("modules")),
+        "rpc_discover" => MethodDoc::new(vec![], object("document")),
+        "parity_devLogs" => {
+            MethodDoc::new(vec![object("filter").optional()],
+                           ContentDescriptor::new("logs", "array"))
+        }
+        "parity_devLogsLevels" => MethodDoc::new(vec![], string("levels")),
+        "parity_setLogLevel" => MethodDoc::new(vec![string("levels")], boolean("success")),
+        "parity_flushCache" => MethodDoc::new(vec![], boolean("enabled")),
+        "pubsub_subscribe" => MethodDoc::new(vec![string("topic")], string("subscriptionId")),
+        "pubsub_unsubscribe" => MethodDoc::new(vec![string("subscriptionId")], boolean("success")),
+        _ => return None,
+    };
+    Some(doc)
+}
+
+/// Generates description of given registered methods.
+/// Methods without known parameters are described with an empty parameter list and a result of any type.
+pub fn document(methods: &MethodApis, modules: &[Module]) -> DiscoverDocument {
+    let docs = modules.iter()
+        .flat_map(|module| module.docs().iter())
+        .collect::<HashMap<_, _>>();
+
+    let mut methods = methods.iter()
+        .map(|(method, api)| {
+            let doc = docs.get(method)
+                .map(|doc| (*doc).clone())
+                .or_else(|| builtin(method))
+                .unwrap_or_else(|| MethodDoc::new(vec![], ContentDescriptor::any("result")));
+
+            MethodDescription {
+                name: method.clone(),
+                params: doc.params,
+                result: doc.result,
+                module: api.name().into(),
+                safety: api.safety(modules).name().into(),
+            }
+        })
+        .collect::<Vec<_>>();
+    methods.sort_by(|a, b| a.name.cmp(&b.name));
+
+    DiscoverDocument {
+        openrpc: OPENRPC_VERSION.into(),
+        info: DiscoverInfo {
+            title: "toyrpc".into(),
+            version: version(),
+        },
+        methods: methods,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use ethcore_logger::RotatingLogger;
+    use serde_json::{self, Value};
+    use types::{ContentDescriptor, Metadata};
+    use api::apis::{setup_apis, Api, ApiSafety, ApiSet, Dependencies, MethodApis, Module};
+    use util::informant::{ClientNotifier, Middleware, RpcStats};
+    use util::subscription_manager::SubscriptionManager;
+    use super::{document, MethodDoc};
+
+    #[test]
+    fn should_describe_builtin_and_custom_methods() {
+        // given
+        let module = Module::new("hello", "1.0", ApiSafety::SemiSafe, HashMap::new())
+            .describe("hello_world",
+                      MethodDoc::new(vec![ContentDescriptor::new("name", "string")],
+                                     ContentDescriptor::new("greeting", "string")));
+        let mut methods = MethodApis::new();
+        methods.insert("web3_sha3".into(), Api::Web3);
+        methods.insert("hello_world".into(), module.api());
+        methods.insert("hello_undocumented".into(), module.api());
+
+        // when
+        let doc = document(&methods, &[module]);
+
+        // then
+        let names = doc.methods.iter().map(|method| method.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["hello_undocumented", "hello_world", "web3_sha3"]);
+
+        let undocumented = &doc.methods[0];
+        assert!(undocumented.params.is_empty());
+        assert_eq!(undocumented.result, ContentDescriptor::any("result"));
+
+        let custom = &doc.methods[1];
+        assert_eq!(custom.module, "hello");
+        assert_eq!(custom.safety, "semi-safe");
+        assert_eq!(custom.params, vec![ContentDescriptor::new("name", "string")]);
+
+        let builtin = &doc.methods[2];
+        assert_eq!(builtin.module, "web3");
+        assert_eq!(builtin.safety, "safe");
+        assert_eq!(builtin.params, vec![ContentDescriptor::new("data", "string")]);
+        assert_eq!(builtin.result, ContentDescriptor::new("hash", "string"));
+    }
+
+    #[test]
+    fn should_describe_every_builtin_method() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+        let deps = Arc::new(Dependencies {
+            logger: Arc::new(RotatingLogger::new("test".to_owned())),
+            subscriptions: Arc::new(SubscriptionManager::new(stats.clone())),
+            network_id: 1,
+            listening: true,
+            modules: vec![],
+            cache: None,
+        });
+        let io = setup_apis(Middleware::new(stats, ClientNotifier::default(), None),
+                            deps,
+                            ApiSet::SafeContext);
+
+        // when
+        let response = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"rpc_discover","params":[],"id":1}"#,
+                                 Metadata::default())
+            .unwrap();
+
+        // then
+        let response: Value = serde_json::from_str(&response).unwrap();
+        let methods = response["result"]["methods"].as_array().unwrap();
+        assert!(methods.iter().any(|method| method["name"] == "rpc_discover"));
+        let undocumented = methods.iter()
+            .filter(|method| method["result"]["schema"].as_object().map_or(true, |schema| schema.is_empty()))
+            .map(|method| method["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(undocumented, Vec::<&str>::new());
+    }
+}
@@ -1 +1,2 @@
 pub mod apis;
+pub mod discovery;
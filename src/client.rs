@@ -0,0 +1,475 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Command-line JSON-RPC client talking to a running node.
+
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use ansi_term::Colour;
+use fetch::Client as FetchClient;
+use futures::Future;
+use serde_json::{self, Map, Value};
+
+#[derive(Debug, PartialEq)]
+pub enum Transport {
+    /// URL of the HTTP server with bearer token authorizing the requests.
+    Http(String, Option<String>),
+    /// Path of the IPC socket.
+    Ipc(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ClientAction {
+    /// Call a method with given params.
+    Call(String, Vec<String>),
+    /// Send calls read from given file as a single batch.
+    Batch(PathBuf),
+    /// Read calls from standard input.
+    Repl,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ClientCmd {
+    pub transport: Transport,
+    pub color: bool,
+    pub action: ClientAction,
+}
+
+/// Executes the command. Returns `Err` if any of the calls failed.
+pub fn execute(cmd: ClientCmd) -> Result<Option<String>, String> {
+    let client = Client::new(cmd.transport)?;
+    let printer = Printer { color: cmd.color && is_tty() };
+
+    match cmd.action {
+        ClientAction::Call(method, params) => {
+            let response = client.send(&call(1, &method, &params))?;
+            printer.response(&response).map(Some)
+        }
+        ClientAction::Batch(path) => {
+            let response = client.send(&Value::Array(read_batch(&path)?))?;
+            printer.response(&response).map(Some)
+        }
+        ClientAction::Repl => repl(&client, &printer).map(|_| None),
+    }
+}
+
+enum Client {
+    Http(FetchClient, String, Option<String>),
+    Ipc(String),
+}
+
+impl Client {
+    fn new(transport: Transport) -> Result<Self, String> {
+        match transport {
+            Transport::Http(url, token) => {
+                let fetch = FetchClient::new().map_err(|e| format!("Unable to create HTTP client: {:?}", e))?;
+                Ok(Client::Http(fetch, url, token))
+            }
+            Transport::Ipc(path) => Ok(Client::Ipc(path)),
+        }
+    }
+
+    /// Sends the request and returns the response (`Value::Null` if there was none).
+    fn send(&self, request: &Value) -> Result<Value, String> {
+        let body = serde_json::to_vec(request).map_err(|e| e.to_string())?;
+        let response = match *self {
+            Client::Http(ref fetch, ref url, ref token) => {
+                let mut response = fetch.post_json_with_token(url, body, token.clone())
+                    .wait()
+                    .map_err(|e| format!("Unable to connect to {}: {:?}", url, e))?;
+                let mut content = String::new();
+                response.read_to_string(&mut content).map_err(|e| e.to_string())?;
+                if !response.is_success() && content.trim().is_empty() {
+                    return Err(format!("Request to {} failed: {}", url, response.status()));
+                }
+                content
+            }
+            Client::Ipc(ref path) => send_ipc(path, &body, expects_response(request))?,
+        };
+
+        match response.trim().is_empty() {
+            true => Ok(Value::Null),
+            false => serde_json::from_str(&response).map_err(|e| format!("Invalid response: {}", e)),
+        }
+    }
+}
+
+/// Returns true if the request contains any call which is not a notification.
+fn expects_response(request: &Value) -> bool {
+    match *request {
+        Value::Array(ref calls) => calls.iter().any(expects_response),
+        ref call => call.get("id").is_some(),
+    }
+}
+
+#[cfg(unix)]
+fn send_ipc(path: &str, body: &[u8], expects_response: bool) -> Result<String, String> {
+    use std::io::BufReader;
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path).map_err(|e| format!("Unable to connect to {}: {}", path, e))?;
+    stream.write_all(body).map_err(|e| e.to_string())?;
+    // the server does not respond to requests containing notifications only
+    if !expects_response {
+        return Ok(String::new());
+    }
+
+    let mut values = serde_json::Deserializer::from_reader(BufReader::new(stream)).into_iter::<Value>();
+    match values.next() {
+        Some(Ok(value)) => Ok(value.to_string()),
+        Some(Err(e)) => Err(format!("Invalid response: {}", e)),
+        None => Ok(String::new()),
+    }
+}
+
+#[cfg(not(unix))]
+fn send_ipc(_path: &str, _body: &[u8], _expects_response: bool) -> Result<String, String> {
+    Err("IPC transport is not supported on this platform, use --url instead.".into())
+}
+
+#[cfg(unix)]
+fn is_tty() -> bool {
+    use libc;
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn is_tty() -> bool {
+    false
+}
+
+/// Parses a param as JSON, falling back to a string if it's not valid JSON.
+fn param(arg: &str) -> Value {
+    serde_json::from_str(arg).unwrap_or_else(|_| Value::String(arg.into()))
+}
+
+fn call<T: AsRef<str>>(id: u64, method: &str, params: &[T]) -> Value {
+    let mut call = Map::new();
+    call.insert("jsonrpc".into(), "2.0".into());
+    call.insert("method".into(), method.into());
+    call.insert("params".into(), Value::Array(params.iter().map(|arg| param(arg.as_ref())).collect()));
+    call.insert("id".into(), id.into());
+    Value::Object(call)
+}
+
+/// Reads calls from a file containing either a JSON array or one call per line.
+/// Missing `jsonrpc` and `id` fields are filled in.
+fn read_batch(path: &Path) -> Result<Vec<Value>, String> {
+    let mut content = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|e| format!("Unable to read batch file {}: {}", path.display(), e))?;
+
+    let calls = match serde_json::from_str(&content) {
+        Ok(Value::Array(calls)) => calls,
+        _ => {
+            content.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| serde_json::from_str(line).map_err(|e| format!("Invalid call {}: {}", line, e)))
+                .collect::<Result<Vec<Value>, _>>()?
+        }
+    };
+
+    calls.into_iter()
+        .enumerate()
+        .map(|(idx, call)| match call {
+            Value::Object(mut call) => {
+                if !call.contains_key("jsonrpc") {
+                    call.insert("jsonrpc".into(), "2.0".into());
+                }
+                if !call.contains_key("id") {
+                    call.insert("id".into(), (idx + 1).into());
+                }
+                Ok(Value::Object(call))
+            }
+            call => Err(format!("Invalid call {}: expected an object.", call)),
+        })
+        .collect()
+}
+
+/// Splits a line into whitespace-separated arguments, keeping JSON strings, arrays and objects together.
+fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in line.chars() {
+        if in_string {
+            current.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                current.push(c);
+            }
+            '[' | '{' => {
+                depth += 1;
+                current.push(c);
+            }
+            ']' | '}' => {
+                depth = depth.saturating_sub(1);
+                current.push(c);
+            }
+            c if c.is_whitespace() && depth == 0 => {
+                if !current.is_empty() {
+                    args.push(mem::replace(&mut current, String::new()));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if in_string || depth > 0 {
+        return Err(format!("Unterminated argument: {}", current));
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+fn repl(client: &Client, printer: &Printer) -> Result<(), String> {
+    let prompt = || {
+        print!("> ");
+        let _ = io::stdout().flush();
+    };
+
+    let stdin = io::stdin();
+    let mut id = 0;
+    prompt();
+    for line in stdin.lock().lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let result = split_args(&line).and_then(|args| match args.split_first() {
+            None => Ok(String::new()),
+            Some((method, _)) if method == "exit" || method == "quit" => Err(String::new()),
+            Some((method, params)) => {
+                id += 1;
+                client.send(&call(id, method, params)).and_then(|response| printer.response(&response))
+            }
+        });
+
+        match result {
+            Ok(ref output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output),
+            Err(ref error) if error.is_empty() => return Ok(()),
+            Err(error) => {
+                let _ = writeln!(&mut io::stderr(), "{}", error);
+            }
+        }
+        prompt();
+    }
+    println!();
+    Ok(())
+}
+
+/// Renders responses as pretty-printed JSON.
+struct Printer {
+    color: bool,
+}
+
+impl Printer {
+    fn paint<S: Into<String>>(&self, colour: Colour, text: S) -> String {
+        let text = text.into();
+        match self.color {
+            true => colour.paint(text).to_string(),
+            false => text,
+        }
+    }
+
+    /// Renders results of all calls, returns `Err` with the whole output if any of the calls failed.
+    fn response(&self, response: &Value) -> Result<String, String> {
+        let (outputs, batch): (Vec<&Value>, bool) = match *response {
+            Value::Array(ref outputs) => (outputs.iter().collect(), true),
+            Value::Null => (Vec::new(), false),
+            ref output => (vec![output], false),
+        };
+
+        let mut failed = false;
+        let rendered = outputs.into_iter()
+            .map(|output| {
+                let id = match batch {
+                    true => format!("{} ", self.paint(Colour::White, format!("[{}]", output["id"]))),
+                    false => String::new(),
+                };
+                match output.get("error") {
+                    Some(error) => {
+                        failed = true;
+                        let mut out = format!("{}{}", id, self.paint(Colour::Red, format!("Error {}: {}",
+                                                                                 error["code"],
+                                                                                 error["message"].as_str().unwrap_or(""))));
+                        if let Some(data) = error.get("data") {
+                            out.push('\n');
+                            self.render(data, 0, &mut out);
+                        }
+                        out
+                    }
+                    None => {
+                        let mut out = id;
+                        self.render(&output["result"], 0, &mut out);
+                        out
+                    }
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        match failed {
+            true => Err(rendered),
+            false => Ok(rendered),
+        }
+    }
+
+    fn render(&self, value: &Value, indent: usize, out: &mut String) {
+        let pad = |indent: usize| "  ".repeat(indent);
+        match *value {
+            Value::Array(ref values) if !values.is_empty() => {
+                out.push_str("[\n");
+                for (idx, value) in values.iter().enumerate() {
+                    out.push_str(&pad(indent + 1));
+                    self.render(value, indent + 1, out);
+                    out.push_str(if idx + 1 < values.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&pad(indent));
+                out.push(']');
+            }
+            Value::Object(ref map) if !map.is_empty() => {
+                out.push_str("{\n");
+                for (idx, (key, value)) in map.iter().enumerate() {
+                    out.push_str(&pad(indent + 1));
+                    out.push_str(&self.paint(Colour::Blue, Value::String(key.clone()).to_string()));
+                    out.push_str(": ");
+                    self.render(value, indent + 1, out);
+                    out.push_str(if idx + 1 < map.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&pad(indent));
+                out.push('}');
+            }
+            Value::String(_) => out.push_str(&self.paint(Colour::Green, value.to_string())),
+            Value::Number(_) => out.push_str(&self.paint(Colour::Cyan, value.to_string())),
+            Value::Bool(_) | Value::Null => out.push_str(&self.paint(Colour::Yellow, value.to_string())),
+            _ => out.push_str(&value.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use rand::random;
+    use serde_json::{self, Value};
+    use super::{split_args, call, read_batch, Printer, Client, Transport};
+
+    #[test]
+    fn should_split_arguments_keeping_json_together() {
+        // when
+        let args = split_args(r#"eth_call {"to": "0x01", "data": [1, 2]}  "a b"  latest"#).unwrap();
+
+        // then
+        assert_eq!(args, vec!["eth_call", r#"{"to": "0x01", "data": [1, 2]}"#, r#""a b""#, "latest"]);
+        assert!(split_args(r#"eth_call {"to": "0x01""#).is_err());
+    }
+
+    #[test]
+    fn should_parse_params_as_json_or_strings() {
+        // when
+        let res = call(7, "web3_sha3", &["0x01", "1", "true", r#""1""#, "[1]"]);
+
+        // then
+        assert_eq!(res["params"], serde_json::from_str::<Value>(r#"["0x01",1,true,"1",[1]]"#).unwrap());
+        assert_eq!(res["method"], Value::from("web3_sha3"));
+        assert_eq!(res["id"], Value::from(7));
+    }
+
+    #[test]
+    fn should_read_batch_file_filling_in_ids() {
+        // given
+        let path = env::temp_dir().join(format!("toyrpc-batch-{}", random::<u64>()));
+        File::create(&path)
+            .unwrap()
+            .write_all(b"{\"method\":\"web3_clientVersion\",\"params\":[]}\n\n{\"method\":\"net_version\",\"params\":[],\"id\":\"a\"}\n")
+            .unwrap();
+
+        // when
+        let res = read_batch(&path);
+        fs::remove_file(&path).unwrap();
+
+        // then
+        let res = res.unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0]["id"], Value::from(1));
+        assert_eq!(res[0]["jsonrpc"], Value::from("2.0"));
+        assert_eq!(res[1]["id"], Value::from("a"));
+    }
+
+    #[test]
+    fn should_fail_if_any_call_failed() {
+        // given
+        let printer = Printer { color: false };
+        let success: Value = serde_json::from_str(r#"{"jsonrpc":"2.0","result":{"a":[1,"b"]},"id":1}"#).unwrap();
+        let batch: Value = serde_json::from_str(r#"[{"jsonrpc":"2.0","result":true,"id":1},
+            {"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":2}]"#).unwrap();
+
+        // when
+        let success = printer.response(&success);
+        let batch = printer.response(&batch);
+
+        // then
+        assert_eq!(success, Ok("{\n  \"a\": [\n    1,\n    \"b\"\n  ]\n}".into()));
+        assert_eq!(batch, Err("[1] true\n[2] Error -32601: Method not found".into()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_call_methods_over_ipc() {
+        use std::os::unix::net::UnixListener;
+        use std::thread;
+
+        // given
+        let path = env::temp_dir().join(format!("toyrpc-client-{}.ipc", random::<u64>()));
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let request = serde_json::Deserializer::from_reader(&stream).into_iter::<Value>().next().unwrap().unwrap();
+            let response = format!(r#"{{"jsonrpc":"2.0","result":{},"id":1}}"#, request["params"][0]);
+            (&stream).write_all(response.as_bytes()).unwrap();
+            (&stream).write_all(b"\n").unwrap();
+            request
+        });
+        let client = Client::new(Transport::Ipc(path.to_string_lossy().into_owned())).unwrap();
+
+        // when
+        let res = client.send(&call(1, "echo", &["0x10"]));
+        let request = server.join().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // then
+        assert_eq!(request["method"], Value::from("echo"));
+        assert_eq!(res.unwrap()["result"], Value::from("0x10"));
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! TOML configuration file.
+
+use std::fs::File;
+use std::io::Read;
+use toml;
+
+/// Configuration file contents. Every value is optional and overridden by command line flags.
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub operating: Operating,
+    pub rpc: Rpc,
+    pub websockets: Websockets,
+    pub ipc: Ipc,
+    pub metrics: Metrics,
+    pub rate_limit: RateLimit,
+    pub timeouts: Timeouts,
+    pub upstream: Upstream,
+    pub cache: Cache,
+    pub logging: Logging,
+}
+
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Operating {
+    pub base_path: Option<String>,
+    pub identity: Option<String>,
+    pub daemon: Option<String>,
+    pub shutdown_timeout: Option<String>,
+    pub network_id: Option<u64>,
+    pub capture: Option<bool>,
+}
+
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rpc {
+    pub disable: Option<bool>,
+    pub port: Option<u16>,
+    pub interface: Option<String>,
+    pub cors: Option<Vec<String>>,
+    pub apis: Option<Vec<String>>,
+    pub hosts: Option<Vec<String>>,
+    pub auth: Option<bool>,
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    pub tls_client_ca: Option<String>,
+}
+
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Websockets {
+    pub disable: Option<bool>,
+    pub port: Option<u16>,
+    pub interface: Option<String>,
+    pub apis: Option<Vec<String>>,
+    pub origins: Option<Vec<String>>,
+    pub hosts: Option<Vec<String>>,
+}
+
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ipc {
+    pub disable: Option<bool>,
+    pub path: Option<String>,
+    pub apis: Option<Vec<String>>,
+}
+
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Metrics {
+    pub port: Option<u16>,
+    pub interface: Option<String>,
+}
+
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub requests_per_second: Option<u32>,
+    pub burst: Option<u32>,
+    pub methods: Option<Vec<String>>,
+}
+
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Timeouts {
+    pub request: Option<String>,
+    pub methods: Option<Vec<String>>,
+}
+
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Upstream {
+    pub urls: Option<Vec<String>>,
+    pub allow: Option<Vec<String>>,
+    pub deny: Option<Vec<String>>,
+}
+
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cache {
+    pub size_mb: Option<usize>,
+}
+
+#[derive(Default, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Logging {
+    pub mode: Option<String>,
+    pub file: Option<String>,
+    pub color: Option<bool>,
+    pub informant_interval: Option<String>,
+}
+
+impl Config {
+    /// Reads and parses configuration file.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| format!("Unable to read config file {}: {}", path, e))?;
+        Self::parse(&contents).map_err(|e| format!("Invalid config file {}: {}", path, e))
+    }
+
+    /// Parses configuration from TOML string.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|e| {
+            let message = e.to_string();
+            let line = if message.contains(" at line ") {
+                None
+            } else {
+                let mut path = key_path(&message);
+                let key = unknown_field(&message).or_else(|| path.pop());
+                key.and_then(|key| line_of(contents, &path.join("."), key))
+            };
+
+            match line {
+                Some(line) => format!("{} at line {}", message, line),
+                None => message,
+            }
+        })
+    }
+
+    /// Returns configuration formatted as TOML.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("Config contains only tables of plain values; qed")
+    }
+}
+
+/// Extracts the key name out of serde's `unknown field` error message.
+fn unknown_field(message: &str) -> Option<&str> {
+    const PREFIX: &'static str = "unknown field `";
+    message.find(PREFIX)
+        .map(|idx| &message[idx + PREFIX.len()..])
+        .and_then(|rest| rest.find('`').map(|len| &rest[..len]))
+}
+
+/// Extracts the dotted path of the failing key out of toml's error message.
+fn key_path(message: &str) -> Vec<&str> {
+    const PREFIX: &'static str = " for key `";
+    message.rfind(PREFIX)
+        .map(|idx| &message[idx + PREFIX.len()..])
+        .and_then(|rest| rest.find('`').map(|len| &rest[..len]))
+        .map_or_else(Vec::new, |path| path.split('.').collect())
+}
+
+/// Returns (1-based) number of the line defining given key or table within given section.
+fn line_of(contents: &str, section: &str, key: &str) -> Option<usize> {
+    let table = match section {
+        "" => key.to_owned(),
+        section => format!("{}.{}", section, key),
+    };
+    let mut current = String::new();
+
+    contents.lines()
+        .position(|line| {
+            let line = line.trim();
+            if line.starts_with('[') {
+                current = line.trim_matches(|c| c == '[' || c == ']').trim().to_owned();
+                return current == table;
+            }
+            current == section && line.starts_with(key) && line[key.len()..].trim_left().starts_with('=')
+        })
+        .map(|idx| idx + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Rpc, Logging};
+
+    #[test]
+    fn should_parse_config_file() {
+        // given
+        let contents = r#"
+[rpc]
+port = 8080
+apis = ["web3", "rpc"]
+
+[logging]
+mode = "rpc=trace"
+"#;
+
+        // when
+        let config = Config::parse(contents).unwrap();
+
+        // then
+        assert_eq!(config,
+                   Config {
+                       rpc: Rpc {
+                           port: Some(8080),
+                           apis: Some(vec!["web3".into(), "rpc".into()]),
+                           ..Default::default()
+                       },
+                       logging: Logging {
+                           mode: Some("rpc=trace".into()),
+                           ..Default::default()
+                       },
+                       ..Default::default()
+                   });
+    }
+
+    #[test]
+    fn should_report_unknown_key_with_line_number() {
+        // given
+        let contents = r#"
+[rpc]
+port = 8080
+prot = 8081
+"#;
+
+        // when
+        let err = Config::parse(contents).unwrap_err();
+
+        // then
+        assert!(err.contains("unknown field `prot`"), "{}", err);
+        assert!(err.contains("at line 4"), "{}", err);
+    }
+
+    #[test]
+    fn should_roundtrip_through_toml() {
+        // given
+        let config = Config {
+            rpc: Rpc {
+                port: Some(8080),
+                hosts: Some(vec!["all".into()]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // when
+        let parsed = Config::parse(&config.to_toml()).unwrap();
+
+        // then
+        assert_eq!(parsed, config);
+    }
+
+    #[test]
+    fn should_report_line_of_key_within_failing_section() {
+        // given
+        let contents = r#"
+[rpc]
+port = 8080
+
+[websockets]
+port = "8546"
+"#;
+
+        // when
+        let err = Config::parse(contents).unwrap_err();
+
+        // then
+        assert!(err.contains("websockets.port"), "{}", err);
+        assert!(err.contains("at line 6"), "{}", err);
+    }
+}
@@ -15,17 +15,35 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::env;
-use rpc::HttpConfiguration;
+use std::ffi::OsString;
+use std::str::FromStr;
+use std::time::Duration;
+use toyrpc::rpc::{HttpConfiguration, WsConfiguration, IpcConfiguration};
 use ethcore_logger::Config as LogConfig;
-use dir::{self, Directories};
+use toyrpc::dir::{self, Directories};
 use run::RunCmd;
-use clap::{Arg, App, Error, ArgMatches};
-use util::misc::version;
+use clap::{Arg, App, SubCommand, Error, ArgMatches};
+use toyrpc::util::misc::version;
+use toyrpc::helpers::{parity_ipc_path, to_duration};
+use config_file::{Config, Operating, Rpc, Websockets, Ipc, Metrics, RateLimit, Timeouts, Upstream, Cache, Logging};
+use toyrpc::util::rate_limit::RateLimitConfiguration;
+use toyrpc::util::timeouts::TimeoutConfiguration;
+use toyrpc::util::upstream::UpstreamConfiguration;
+use toyrpc::util::response_cache::CacheConfiguration;
+use toyrpc::metrics::MetricsConfiguration;
+use toyrpc::tokens::{TokensCmd, TokensAction};
+use toyrpc::replay::ReplayCmd;
+use toyrpc::client::{ClientCmd, ClientAction, Transport};
+use toyrpc::tls::TlsConfiguration;
 
 #[derive(Debug, PartialEq)]
 pub enum Cmd {
     Run(RunCmd),
     Version,
+    DumpConfig(String),
+    Tokens(TokensCmd),
+    Replay(ReplayCmd),
+    Client(ClientCmd),
 }
 
 pub struct Execute {
@@ -45,7 +63,13 @@ fn get_rpc_port() -> u16 {
 
 impl<'a> Configuration<'a> {
     pub fn parse() -> Result<Self, Error> {
+        Self::parse_from(env::args_os())
+    }
 
+    pub fn parse_from<I, T>(args: I) -> Result<Self, Error>
+        where I: IntoIterator<Item = T>,
+              T: Into<OsString> + Clone
+    {
         let matches = App::new("Toy json rpc").version("1.0")
             .author("Marcos Macedo <contato@mmacedo.eu.org>")
             .about("Toy json rpc starter kit")
@@ -65,6 +89,10 @@ impl<'a> Configuration<'a> {
                 .help("Sets a custom config file")
                 .takes_value(true)
                 .group("Miscellaneous"))
+            .arg(Arg::with_name("dump-config")
+                .long("dump-config")
+                .help("Print the effective configuration, merged from the config
+                                 file and command line, and exit."))
             .arg(Arg::with_name("logging")
                 .short("l")
                 .long("logging")
@@ -79,10 +107,16 @@ impl<'a> Configuration<'a> {
                 .help("Specify a filename into which logging should be
                                  appended. [default: None]")
                 .group("Miscellaneous"))
-            .arg(Arg::with_name("no-color")
+            .arg(Self::switch("no-color")
                 .long("no-color")
                 .help("Don't use terminal color codes in output. [default: false]")
                 .group("Miscellaneous"))
+            .arg(Arg::with_name("informant-interval")
+                .long("informant-interval")
+                .value_name("DURATION")
+                .help("Specify how often the status line is logged, e.g.
+                                 5seconds, minute.")
+                .default_value("5seconds"))
             .arg(Arg::with_name("base-path")
                 .short("d")
                 .long("base-path")
@@ -95,7 +129,23 @@ impl<'a> Configuration<'a> {
                 .help("Specify your node's name.")
                 .default_value("")
                 .group("operation"))
-            .arg(Arg::with_name("no-jsonrpc")
+            .arg(Arg::with_name("network-id")
+                .long("network-id")
+                .value_name("INDEX")
+                .help("Specify the network id reported by net_version.")
+                .default_value("1"))
+            .arg(Arg::with_name("shutdown-timeout")
+                .long("shutdown-timeout")
+                .value_name("DURATION")
+                .help("Specify how long to wait for in-flight requests to
+                                 finish when shutting down, e.g. 30seconds.")
+                .default_value("30seconds"))
+            .arg(Self::switch("capture")
+                .long("capture")
+                .help("Append every processed request and its response to
+                                 capture.ndjson in the base path, for use with
+                                 the replay command. [default: false]"))
+            .arg(Self::switch("no-jsonrpc")
                 .long("no-jsonrpc")
                 .help("Disable the JSON-RPC API server. [default: false]"))
             .arg(Arg::with_name("jsonrpc-port")
@@ -121,7 +171,8 @@ impl<'a> Configuration<'a> {
                 .help("Specify the APIs available through the JSONRPC
                                  interface. APIS is a comma-delimited list of API
                                  name. Possible name are web3, eth, net, personal,
-                                 parity, parity_set, traces, rpc, parity_accounts.")
+                                 parity, parity_set, traces, rpc, parity_accounts,
+                                 pubsub.")
                 .default_value("web3,eth,net,parity,traces,rpc"))
             .arg(Arg::with_name("jsonrpc-hosts")
                 .long("jsonrpc-hosts")
@@ -131,26 +182,303 @@ impl<'a> Configuration<'a> {
                                  is additional security against some attack
                                  vectors. Special options: "all", "none""#)
                 .default_value("none"))
+            .arg(Self::switch("jsonrpc-auth")
+                .long("jsonrpc-auth")
+                .help("Require a bearer token (see `tokens generate`) to call
+                                 APIs outside of the safe set over HTTP and WebSockets.
+                                 WebSocket clients pass it as `bearer.<token>` subprotocol.
+                                 [default: false]"))
+            .arg(Arg::with_name("jsonrpc-tls-cert")
+                .long("jsonrpc-tls-cert")
+                .value_name("FILE")
+                .help("Serve JSON-RPC over HTTPS using given PEM encoded
+                                 certificate. Requires --jsonrpc-tls-key."))
+            .arg(Arg::with_name("jsonrpc-tls-key")
+                .long("jsonrpc-tls-key")
+                .value_name("FILE")
+                .help("Specify PEM encoded private key of the HTTPS
+                                 certificate."))
+            .arg(Arg::with_name("jsonrpc-tls-client-ca")
+                .long("jsonrpc-tls-client-ca")
+                .value_name("FILE")
+                .help("Require clients to present a certificate signed by
+                                 one of the PEM encoded CA certificates in FILE."))
+            .arg(Self::switch("no-ws")
+                .long("no-ws")
+                .help("Disable the WebSockets server. [default: false]"))
+            .arg(Arg::with_name("ws-port")
+                .long("ws-port")
+                .value_name("PORT")
+                .help("Specify the port portion of the WebSockets server.")
+                .default_value("8546"))
+            .arg(Arg::with_name("ws-interface")
+                .long("ws-interface")
+                .value_name("IP")
+                .help("Specify the hostname portion of the WebSockets
+                                 server, IP should be an interface's IP address, or
+                                 all (all interfaces) or local.")
+                .default_value("local"))
+            .arg(Arg::with_name("ws-apis")
+                .long("ws-apis")
+                .value_name("APIS")
+                .help("Specify the APIs available through the WebSockets
+                                 interface. APIS is a comma-delimited list of API
+                                 name. Possible name are web3, eth, net, personal,
+                                 parity, parity_set, traces, rpc, parity_accounts,
+                                 pubsub.")
+                .default_value("web3,eth,net,parity,traces,rpc,pubsub"))
+            .arg(Arg::with_name("ws-origins")
+                .long("ws-origins")
+                .value_name("URL")
+                .help(r#"Specify Origin header values allowed to connect.
+                                 Special options: "all", "none"."#)
+                .default_value("none"))
+            .arg(Arg::with_name("ws-hosts")
+                .long("ws-hosts")
+                .value_name("HOSTS")
+                .help(r#"List of allowed Host header values. This option will
+                                 validate the Host header sent by the browser, it
+                                 is additional security against some attack
+                                 vectors. Special options: "all", "none"."#)
+                .default_value("none"))
+            .arg(Self::switch("no-ipc")
+                .long("no-ipc")
+                .help("Disable JSON-RPC over IPC service. [default: false]"))
+            .arg(Arg::with_name("ipc-path")
+                .long("ipc-path")
+                .value_name("PATH")
+                .help("Specify custom path for JSON-RPC over IPC service.")
+                .default_value("$BASE/jsonrpc.ipc"))
+            .arg(Arg::with_name("ipc-apis")
+                .long("ipc-apis")
+                .value_name("APIS")
+                .help("Specify custom API set available via JSON-RPC over
+                                 IPC.")
+                .default_value("web3,eth,net,parity,parity_accounts,traces,rpc,pubsub"))
+            .arg(Arg::with_name("metrics-port")
+                .long("metrics-port")
+                .value_name("PORT")
+                .help("Serve Prometheus metrics on given port. [default: disabled]"))
+            .arg(Arg::with_name("metrics-interface")
+                .long("metrics-interface")
+                .value_name("IP")
+                .help("Specify the hostname portion of the metrics server,
+                                 IP should be an interface's IP address, or all
+                                 (all interfaces) or local.")
+                .default_value("local"))
+            .arg(Arg::with_name("rate-limit")
+                .long("rate-limit")
+                .value_name("RPS")
+                .help("Specify number of calls per second allowed for a
+                                 single origin. [default: unlimited]"))
+            .arg(Arg::with_name("rate-limit-burst")
+                .long("rate-limit-burst")
+                .value_name("CALLS")
+                .help("Specify number of calls allowed in a single burst.
+                                 [default: same as rate]"))
+            .arg(Arg::with_name("rate-limit-methods")
+                .long("rate-limit-methods")
+                .value_name("LIST")
+                .help("Specify per-method limits of calls per second for a
+                                 single origin, as a comma-delimited list of
+                                 method=rate pairs, e.g. web3_sha3=10."))
+            .arg(Arg::with_name("request-timeout")
+                .long("request-timeout")
+                .value_name("DURATION")
+                .help("Specify how long a request may run before it is
+                                 cancelled, e.g. 30seconds. 0 disables the limit.")
+                .default_value("1minute"))
+            .arg(Arg::with_name("request-timeout-methods")
+                .long("request-timeout-methods")
+                .value_name("LIST")
+                .help("Specify per-method request timeouts as a
+                                 comma-delimited list of method=duration pairs,
+                                 e.g. parity_devLogs=5seconds."))
+            .arg(Arg::with_name("response-cache-size")
+                .long("response-cache-size")
+                .value_name("MB")
+                .help("Cache responses to idempotent methods using up to given
+                                 amount of memory in megabytes. [default: disabled]"))
+            .arg(Arg::with_name("upstream")
+                .long("upstream")
+                .value_name("URLS")
+                .help("Forward calls of methods not served locally to given
+                                 JSON-RPC nodes, as a comma-delimited list of URLs
+                                 in order of preference. [default: none]"))
+            .arg(Arg::with_name("upstream-allow")
+                .long("upstream-allow")
+                .value_name("METHODS")
+                .help("Specify methods which may be forwarded upstream, as a
+                                 comma-delimited list of names, * matches any
+                                 characters. Methods outside of public chain reads
+                                 are forwarded only for callers with a valid token
+                                 or over IPC. [default: eth_get*, eth_call, net_*, ...]"))
+            .arg(Arg::with_name("upstream-deny")
+                .long("upstream-deny")
+                .value_name("METHODS")
+                .help("Specify methods which are never forwarded upstream, in
+                                 addition to methods exposing accounts or changing
+                                 node settings. [default: none]"))
+            .subcommand(SubCommand::with_name("tokens")
+                .about("Manage authorization tokens for the JSON-RPC API.")
+                .subcommand(SubCommand::with_name("generate")
+                    .about("Generate a new token.")
+                    .arg(Arg::with_name("apis")
+                        .long("apis")
+                        .value_name("APIS")
+                        .help("Specify the APIs the token gives access to.")
+                        .default_value("parity_accounts,parity_set,signer")))
+                .subcommand(SubCommand::with_name("list")
+                    .about("List existing tokens."))
+                .subcommand(SubCommand::with_name("revoke")
+                    .about("Revoke given token.")
+                    .arg(Arg::with_name("TOKEN")
+                        .required(true))))
+            .subcommand(SubCommand::with_name("replay")
+                .about("Replay captured requests and report responses that differ from the recorded ones.")
+                .arg(Arg::with_name("FILE")
+                    .help("Capture file to replay. [default: capture.ndjson in the base path]")))
+            .subcommand(SubCommand::with_name("call")
+                .about("Call a method of a running node and print its result.")
+                .arg(Arg::with_name("METHOD")
+                    .required_unless("batch")
+                    .help("Method to call."))
+                .arg(Arg::with_name("PARAMS")
+                    .multiple(true)
+                    .allow_hyphen_values(true)
+                    .help("Params of the call, each parsed as JSON or taken as a string if it is not valid JSON."))
+                .arg(Arg::with_name("batch")
+                    .long("batch")
+                    .value_name("FILE")
+                    .conflicts_with("METHOD")
+                    .help("Send calls read from given file, a JSON array or one call per line, as a single batch."))
+                .args(&Self::client_args()))
+            .subcommand(SubCommand::with_name("repl")
+                .about("Read calls from standard input, one `method params...` per line, and print their results.")
+                .args(&Self::client_args()))
             .version(version().as_str())
-            .get_matches_safe()?;
+            .get_matches_from_safe(args)?;
 
         let config = Configuration { args: matches };
         Ok(config)
     }
 
+    /// On/off flag. Given without a value it turns the option on,
+    /// `--flag=false` turns off an option enabled in the config file.
+    fn switch(name: &'static str) -> Arg<'static, 'static> {
+        Arg::with_name(name)
+            .value_name("BOOL")
+            .takes_value(true)
+            .min_values(0)
+            .require_equals(true)
+            .possible_values(&["true", "false"])
+    }
+
+    /// Arguments selecting the node `call` and `repl` talk to.
+    fn client_args() -> Vec<Arg<'static, 'static>> {
+        vec![Arg::with_name("url")
+                 .long("url")
+                 .value_name("URL")
+                 .help("Send requests to given HTTP server. [default: the configured JSON-RPC server]"),
+             Arg::with_name("ipc")
+                 .long("ipc")
+                 .conflicts_with("url")
+                 .help("Send requests over the configured IPC socket."),
+             Arg::with_name("token")
+                 .long("token")
+                 .value_name("TOKEN")
+                 .conflicts_with("ipc")
+                 .help("Authorize HTTP requests with given bearer token.")]
+    }
+
     pub fn into_command(self) -> Result<Execute, String> {
-        let dirs = self.directories();
-        let logger_config = self.logger_config();
-        let http_conf = self.http_config()?;
+        let file = match self.args.value_of("config") {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        let config = self.merge(file)?;
+        let logger_config = Self::logger_config(&config);
+
+        if self.args.is_present("dump-config") {
+            return Ok(Execute {
+                logger: logger_config,
+                cmd: Cmd::DumpConfig(config.to_toml()),
+            });
+        }
+
+        let dirs = Self::directories(&config);
+        if let Some(tokens) = self.args.subcommand_matches("tokens") {
+            return Ok(Execute {
+                logger: logger_config,
+                cmd: Cmd::Tokens(TokensCmd {
+                    dirs: dirs,
+                    action: Self::tokens_action(tokens)?,
+                }),
+            });
+        }
+
+        if let Some(replay) = self.args.subcommand_matches("replay") {
+            return Ok(Execute {
+                logger: logger_config,
+                cmd: Cmd::Replay(ReplayCmd {
+                    file: replay.value_of("FILE").map_or_else(|| dirs.capture_path(), Into::into),
+                    network_id: config.operating.network_id.unwrap_or(1),
+                    http_apis: Self::http_config(&config)?.apis,
+                    ws_apis: Self::ws_config(&config)?.apis,
+                    ipc_apis: Self::ipc_config(&config)?.apis,
+                }),
+            });
+        }
+
+        if let Some(call) = self.args.subcommand_matches("call") {
+            let action = match call.value_of("batch") {
+                Some(file) => ClientAction::Batch(file.into()),
+                None => {
+                    let method = call.value_of("METHOD").expect("METHOD is required unless batch is given; qed");
+                    let params = call.values_of("PARAMS").map_or_else(Vec::new, |params| params.map(Into::into).collect());
+                    ClientAction::Call(method.into(), params)
+                }
+            };
+            return Ok(Execute {
+                cmd: Cmd::Client(Self::client_cmd(&config, call, action, logger_config.color)?),
+                logger: logger_config,
+            });
+        }
+
+        if let Some(repl) = self.args.subcommand_matches("repl") {
+            return Ok(Execute {
+                cmd: Cmd::Client(Self::client_cmd(&config, repl, ClientAction::Repl, logger_config.color)?),
+                logger: logger_config,
+            });
+        }
+
+        let http_conf = Self::http_config(&config)?;
+        let ws_conf = Self::ws_config(&config)?;
+        let ipc_conf = Self::ipc_config(&config)?;
+        let metrics_conf = Self::metrics_config(&config);
+        let rate_limit_conf = Self::rate_limit_config(&config)?;
+        let timeout_conf = Self::timeout_config(&config)?;
+        let upstream_conf = Self::upstream_config(&config);
+        let cache_conf = Self::cache_config(&config)?;
 
         let cmd = {
-            let daemon = self.args.value_of("daemon");
             let run_cmd = RunCmd {
                 dirs: dirs,
-                daemon: daemon.map(str::to_string),
+                daemon: config.operating.daemon.clone(),
                 logger_config: logger_config.clone(),
                 http_conf: http_conf,
-                name: self.args.value_of("identity").map(str::to_string).unwrap(),
+                ws_conf: ws_conf,
+                ipc_conf: ipc_conf,
+                metrics_conf: metrics_conf,
+                rate_limit_conf: rate_limit_conf,
+                timeout_conf: timeout_conf,
+                upstream_conf: upstream_conf,
+                cache_conf: cache_conf,
+                informant_interval: Self::informant_interval(&config)?,
+                shutdown_timeout: Self::shutdown_timeout(&config)?,
+                network_id: config.operating.network_id.unwrap_or(1),
+                capture: config.operating.capture.unwrap_or(false),
+                name: config.operating.identity.clone().unwrap_or_default(),
             };
             Cmd::Run(run_cmd)
         };
@@ -161,25 +489,140 @@ impl<'a> Configuration<'a> {
         })
     }
 
-    fn logger_config(&self) -> LogConfig {
+    fn tokens_action(args: &ArgMatches) -> Result<TokensAction, String> {
+        match args.subcommand() {
+            ("generate", Some(generate)) => {
+                Ok(TokensAction::Generate(generate.value_of("apis").unwrap_or_default().parse()?))
+            }
+            ("list", _) => Ok(TokensAction::List),
+            ("revoke", Some(revoke)) => {
+                Ok(TokensAction::Revoke(revoke.value_of("TOKEN").expect("TOKEN is required; qed").into()))
+            }
+            _ => Err("Unknown tokens command. Use one of: generate, list, revoke.".into()),
+        }
+    }
+
+    /// Returns value given on the command line, falling back to the config file value
+    /// and then to the argument default.
+    fn value(&self, name: &str, file: Option<String>) -> Option<String> {
+        match self.args.occurrences_of(name) {
+            0 => file.or_else(|| self.args.value_of(name).map(str::to_string)),
+            _ => self.args.value_of(name).map(str::to_string),
+        }
+    }
+
+    fn list(&self, name: &str, file: Option<Vec<String>>) -> Option<Vec<String>> {
+        self.value(name, file.map(|list| list.join(",")))
+            .map(|list| list.split(',').map(Into::into).collect())
+    }
+
+    fn number<T: FromStr + ToString>(&self, name: &str, file: Option<T>) -> Result<Option<T>, String> {
+        match self.value(name, file.map(|number| number.to_string())) {
+            Some(number) => number.parse().map(Some).map_err(|_| format!("Invalid {} given: {}", name, number)),
+            None => Ok(None),
+        }
+    }
+
+    fn flag(&self, name: &str, file: Option<bool>) -> Option<bool> {
+        match self.args.occurrences_of(name) {
+            0 => Some(file.unwrap_or(false)),
+            _ => Some(self.args.value_of(name).map_or(true, |value| value == "true")),
+        }
+    }
+
+    /// Merges config file with command line. Command line flags take precedence.
+    fn merge(&self, file: Config) -> Result<Config, String> {
+        let Config { operating, rpc, websockets, ipc, metrics, rate_limit, timeouts, upstream, cache, logging } = file;
+
+        Ok(Config {
+            operating: Operating {
+                base_path: self.value("base-path", operating.base_path)
+                    .or_else(|| Some(dir::default_data_path())),
+                identity: self.value("identity", operating.identity),
+                daemon: self.value("daemon", operating.daemon),
+                shutdown_timeout: self.value("shutdown-timeout", operating.shutdown_timeout),
+                network_id: self.number("network-id", operating.network_id)?,
+                capture: self.flag("capture", operating.capture),
+            },
+            rpc: Rpc {
+                disable: self.flag("no-jsonrpc", rpc.disable),
+                port: Some(self.number("jsonrpc-port", rpc.port)?.unwrap_or_else(get_rpc_port)),
+                interface: self.value("jsonrpc-interface", rpc.interface),
+                cors: self.list("jsonrpc-cors", rpc.cors),
+                apis: self.list("jsonrpc-apis", rpc.apis),
+                hosts: self.list("jsonrpc-hosts", rpc.hosts),
+                auth: self.flag("jsonrpc-auth", rpc.auth),
+                tls_cert: self.value("jsonrpc-tls-cert", rpc.tls_cert),
+                tls_key: self.value("jsonrpc-tls-key", rpc.tls_key),
+                tls_client_ca: self.value("jsonrpc-tls-client-ca", rpc.tls_client_ca),
+            },
+            websockets: Websockets {
+                disable: self.flag("no-ws", websockets.disable),
+                port: self.number("ws-port", websockets.port)?,
+                interface: self.value("ws-interface", websockets.interface),
+                apis: self.list("ws-apis", websockets.apis),
+                origins: self.list("ws-origins", websockets.origins),
+                hosts: self.list("ws-hosts", websockets.hosts),
+            },
+            ipc: Ipc {
+                disable: self.flag("no-ipc", ipc.disable),
+                path: self.value("ipc-path", ipc.path),
+                apis: self.list("ipc-apis", ipc.apis),
+            },
+            metrics: Metrics {
+                port: self.number("metrics-port", metrics.port)?,
+                interface: self.value("metrics-interface", metrics.interface),
+            },
+            rate_limit: RateLimit {
+                requests_per_second: self.number("rate-limit", rate_limit.requests_per_second)?,
+                burst: self.number("rate-limit-burst", rate_limit.burst)?,
+                methods: self.list("rate-limit-methods", rate_limit.methods),
+            },
+            timeouts: Timeouts {
+                request: self.value("request-timeout", timeouts.request),
+                methods: self.list("request-timeout-methods", timeouts.methods),
+            },
+            upstream: Upstream {
+                urls: self.list("upstream", upstream.urls),
+                allow: self.list("upstream-allow", upstream.allow),
+                deny: self.list("upstream-deny", upstream.deny),
+            },
+            cache: Cache {
+                size_mb: self.number("response-cache-size", cache.size_mb)?,
+            },
+            logging: Logging {
+                mode: self.value("logging", logging.mode),
+                file: self.value("log-file", logging.file),
+                color: self.flag("no-color", logging.color.map(|color| !color)).map(|no_color| !no_color),
+                informant_interval: self.value("informant-interval", logging.informant_interval),
+            },
+        })
+    }
+
+    fn logger_config(config: &Config) -> LogConfig {
         LogConfig {
-            mode: self.args.value_of("logging").map(str::to_string),
-            color: !self.args.is_present("no-color") && !cfg!(windows),
-            file: self.args.value_of("log-file").map(str::to_string),
+            mode: config.logging.mode.clone(),
+            color: config.logging.color.unwrap_or(true) && !cfg!(windows),
+            file: config.logging.file.clone(),
         }
     }
 
-    fn rpc_apis(&self) -> String {
-        self.args.value_of("jsonrpc-apis").map(str::to_string).unwrap()
+    fn informant_interval(config: &Config) -> Result<Duration, String> {
+        let interval = config.logging.informant_interval.as_ref().map_or("5seconds", String::as_str);
+        match to_duration(interval)? {
+            duration if duration == Duration::from_secs(0) => {
+                Err(format!("Invalid informant interval given: {}", interval))
+            }
+            duration => Ok(duration),
+        }
     }
 
-    fn cors(cors: Option<&str>) -> Option<Vec<String>> {
-        cors.map(|c| c.split(',').map(Into::into).collect())
+    fn shutdown_timeout(config: &Config) -> Result<Duration, String> {
+        to_duration(config.operating.shutdown_timeout.as_ref().map_or("30seconds", String::as_str))
     }
 
-    fn rpc_cors(&self) -> Option<Vec<String>> {
-        let cors = self.args.value_of("jsonrpc-cors");
-        Self::cors(cors)
+    fn joined(list: &Option<Vec<String>>) -> String {
+        list.as_ref().map(|list| list.join(",")).unwrap_or_default()
     }
 
     fn hosts(hosts: &str) -> Option<Vec<String>> {
@@ -192,31 +635,149 @@ impl<'a> Configuration<'a> {
         Some(hosts)
     }
 
-    fn rpc_hosts(&self) -> Option<Vec<String>> {
-        Self::hosts(self.args.value_of("jsonrpc-hosts").unwrap())
+    fn http_config(config: &Config) -> Result<HttpConfiguration, String> {
+        let rpc = &config.rpc;
+        let conf = HttpConfiguration {
+            enabled: !rpc.disable.unwrap_or(false),
+            interface: Self::interface(rpc.interface.as_ref().map_or("local", String::as_str)),
+            port: rpc.port.unwrap_or_else(get_rpc_port),
+            apis: Self::joined(&rpc.apis).parse()?,
+            hosts: Self::hosts(&Self::joined(&rpc.hosts)),
+            cors: rpc.cors.clone(),
+            auth: rpc.auth.unwrap_or(false),
+            tls: Self::tls_config(rpc)?,
+        };
+
+        Ok(conf)
     }
 
-    fn http_config(&self) -> Result<HttpConfiguration, String> {
-        let m = &self.args;
-        let conf = HttpConfiguration {
-            enabled: !self.args.is_present("no-jsonrpc"),
-            interface: self.rpc_interface(),
-            port: value_t!(m, "jsonrpc-port", u16).unwrap_or(get_rpc_port()),
-            apis: self.rpc_apis().parse()?,
-            hosts: self.rpc_hosts(),
-            cors: self.rpc_cors(),
+    fn tls_config(rpc: &Rpc) -> Result<Option<TlsConfiguration>, String> {
+        match (rpc.tls_cert.clone(), rpc.tls_key.clone()) {
+            (Some(cert), Some(key)) => {
+                Ok(Some(TlsConfiguration {
+                    cert: cert,
+                    key: key,
+                    client_ca: rpc.tls_client_ca.clone(),
+                }))
+            }
+            (None, None) if rpc.tls_client_ca.is_none() => Ok(None),
+            _ => Err("Both --jsonrpc-tls-cert and --jsonrpc-tls-key are required to enable TLS.".into()),
+        }
+    }
+
+    fn ws_config(config: &Config) -> Result<WsConfiguration, String> {
+        let ws = &config.websockets;
+        let conf = WsConfiguration {
+            enabled: !ws.disable.unwrap_or(false),
+            interface: Self::interface(ws.interface.as_ref().map_or("local", String::as_str)),
+            port: ws.port.unwrap_or(8546),
+            apis: Self::joined(&ws.apis).parse()?,
+            origins: Self::hosts(&Self::joined(&ws.origins)),
+            hosts: Self::hosts(&Self::joined(&ws.hosts)),
+            auth: config.rpc.auth.unwrap_or(false),
+        };
+
+        Ok(conf)
+    }
+
+    fn ipc_config(config: &Config) -> Result<IpcConfiguration, String> {
+        let ipc = &config.ipc;
+        let conf = IpcConfiguration {
+            enabled: !ipc.disable.unwrap_or(false),
+            socket_addr: Self::ipc_path(config),
+            apis: Self::joined(&ipc.apis).parse()?,
         };
 
         Ok(conf)
     }
 
-    fn directories(&self) -> Directories {
-        let base = dir::default_data_path();
-        let base_path = self.args
-            .value_of("PATH")
-            .unwrap_or(&base);
+    fn metrics_config(config: &Config) -> MetricsConfiguration {
+        let metrics = &config.metrics;
+        MetricsConfiguration {
+            enabled: metrics.port.is_some(),
+            interface: Self::interface(metrics.interface.as_ref().map_or("local", String::as_str)),
+            port: metrics.port.unwrap_or_else(|| MetricsConfiguration::default().port),
+        }
+    }
 
-        Directories { base: base_path.into() }
+    fn rate_limit_config(config: &Config) -> Result<RateLimitConfiguration, String> {
+        let rate_limit = &config.rate_limit;
+        if rate_limit.requests_per_second == Some(0) {
+            return Err("Invalid rate limit given: 0. Expected positive number of requests per second.".into());
+        }
+        Ok(RateLimitConfiguration {
+            requests_per_second: rate_limit.requests_per_second,
+            burst: rate_limit.burst,
+            methods: RateLimitConfiguration::parse_methods(rate_limit.methods.as_ref().map_or(&[], Vec::as_slice))?,
+        })
+    }
+
+    fn timeout_config(config: &Config) -> Result<TimeoutConfiguration, String> {
+        let timeouts = &config.timeouts;
+        Ok(TimeoutConfiguration {
+            request: Some(to_duration(timeouts.request.as_ref().map_or("1minute", String::as_str))?),
+            methods: TimeoutConfiguration::parse_methods(timeouts.methods.as_ref().map_or(&[], Vec::as_slice))?,
+        })
+    }
+
+    fn upstream_config(config: &Config) -> UpstreamConfiguration {
+        let upstream = &config.upstream;
+        UpstreamConfiguration {
+            urls: upstream.urls.clone().unwrap_or_default(),
+            allow: upstream.allow.clone(),
+            deny: upstream.deny.clone().unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    fn cache_config(config: &Config) -> Result<CacheConfiguration, String> {
+        match config.cache.size_mb {
+            Some(size_mb) => {
+                let size = size_mb.checked_mul(1024 * 1024)
+                    .ok_or_else(|| format!("Invalid response cache size given: {}MB. The size is too large.", size_mb))?;
+                Ok(CacheConfiguration {
+                    enabled: true,
+                    size: size,
+                })
+            }
+            None => Ok(CacheConfiguration::default()),
+        }
+    }
+
+    fn client_cmd(config: &Config, args: &ArgMatches, action: ClientAction, color: bool) -> Result<ClientCmd, String> {
+        let token = args.value_of("token").map(Into::into);
+        let transport = match (args.value_of("url"), args.is_present("ipc")) {
+            (Some(url), _) => Transport::Http(url.into(), token),
+            (None, true) => Transport::Ipc(Self::ipc_path(config)),
+            (None, false) => {
+                let http = Self::http_config(config)?;
+                let scheme = if http.tls.is_some() { "https" } else { "http" };
+                let interface = match http.interface.as_str() {
+                    "0.0.0.0" => "127.0.0.1".into(),
+                    "::" => "[::1]".into(),
+                    interface if interface.contains(':') => format!("[{}]", interface),
+                    interface => interface.into(),
+                };
+                Transport::Http(format!("{}://{}:{}", scheme, interface, http.port), token)
+            }
+        };
+
+        Ok(ClientCmd {
+            transport: transport,
+            color: color,
+            action: action,
+        })
+    }
+
+    fn ipc_path(config: &Config) -> String {
+        let path = config.ipc.path.as_ref().map_or("$BASE/jsonrpc.ipc", String::as_str);
+        parity_ipc_path(&Self::directories(config).base, path)
+    }
+
+    fn directories(config: &Config) -> Directories {
+        let base_path = config.operating.base_path.clone().unwrap_or_else(dir::default_data_path);
+
+        Directories { base: base_path }
     }
 
     fn interface(interface: &str) -> String {
@@ -227,8 +788,83 @@ impl<'a> Configuration<'a> {
             }
             .into()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use config_file::Config;
+    use toyrpc::client::{ClientAction, Transport};
+    use super::Configuration;
+
+    #[test]
+    fn should_prefer_command_line_over_config_file() {
+        // given
+        let file = Config::parse(r#"
+[rpc]
+disable = true
+port = 8080
+auth = true
+
+[ipc]
+disable = true
+"#)
+            .unwrap();
+        let conf = Configuration::parse_from(vec!["toyrpc", "--no-jsonrpc=false", "--jsonrpc-port", "9090", "--no-ws"])
+            .unwrap();
+
+        // when
+        let config = conf.merge(file).unwrap();
+
+        // then
+        assert_eq!(config.rpc.disable, Some(false));
+        assert_eq!(config.rpc.port, Some(9090));
+        assert_eq!(config.rpc.auth, Some(true));
+        assert_eq!(config.websockets.disable, Some(true));
+        assert_eq!(config.ipc.disable, Some(true));
+    }
+
+    #[test]
+    fn should_reject_zero_informant_interval() {
+        // given
+        let conf = Configuration::parse_from(vec!["toyrpc", "--informant-interval", "0seconds"]).unwrap();
+
+        // when
+        let config = conf.merge(Config::default()).unwrap();
+
+        // then
+        assert!(Configuration::informant_interval(&config).is_err());
+    }
+
+    #[test]
+    fn should_reject_overflowing_response_cache_size() {
+        // given
+        let size = ::std::usize::MAX.to_string();
+        let conf = Configuration::parse_from(vec!["toyrpc", "--response-cache-size", size.as_str()]).unwrap();
+
+        // when
+        let config = conf.merge(Config::default()).unwrap();
+
+        // then
+        assert!(Configuration::cache_config(&config).is_err());
+    }
+
+    #[test]
+    fn should_bracket_ipv6_interface_in_client_url() {
+        // given
+        let file = Config::parse(r#"
+[rpc]
+interface = "::1"
+port = 8545
+"#)
+            .unwrap();
+        let conf = Configuration::parse_from(vec!["toyrpc", "repl", "--token", "secret"]).unwrap();
+        let config = conf.merge(file).unwrap();
+        let args = conf.args.subcommand_matches("repl").unwrap();
+
+        // when
+        let cmd = Configuration::client_cmd(&config, args, ClientAction::Repl, false).unwrap();
 
-    fn rpc_interface(&self) -> String {
-        Self::interface(self.args.value_of("jsonrpc-interface").unwrap())
+        // then
+        assert_eq!(cmd.transport, Transport::Http("http://[::1]:8545".into(), Some("secret".into())));
     }
 }
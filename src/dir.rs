@@ -43,7 +43,7 @@ pub const CHAINS_PATH: &'static str = "$LOCAL/chains";
 #[cfg(not(target_os = "windows"))]
 pub const CHAINS_PATH: &'static str = "$BASE/chains";
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Directories {
     pub base: String,
 }
@@ -73,6 +73,20 @@ impl Directories {
         dir
     }
 
+    /// Get the authorization tokens file path
+    pub fn tokens_path(&self) -> PathBuf {
+        let mut path = Path::new(&self.base).to_path_buf();
+        path.push("tokens.json");
+        path
+    }
+
+    /// Get the captured JSON-RPC traffic file path
+    pub fn capture_path(&self) -> PathBuf {
+        let mut path = Path::new(&self.base).to_path_buf();
+        path.push("capture.ndjson");
+        path
+    }
+
     // TODO: remove in 1.7
     pub fn legacy_keys_path(&self, testnet: bool) -> PathBuf {
         let mut dir = Path::new(&self.base).to_path_buf();
@@ -0,0 +1,138 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Health and readiness probes served by the HTTP server.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use futures::{future, Future};
+use jsonrpc_http_server::{hyper, RequestMiddleware, RequestMiddlewareAction, Response};
+use serde_json;
+use types::{HealthStatus, RpcStatsInfo};
+use util::informant::RpcStats;
+use util::misc::version;
+
+/// Tracks uptime and readiness of the node.
+pub struct Health {
+    started: Instant,
+    ready: AtomicBool,
+    stats: Arc<RpcStats>,
+}
+
+impl Health {
+    /// Creates new `Health`. The node is not ready until `set_ready` is called.
+    pub fn new(stats: Arc<RpcStats>) -> Self {
+        Health {
+            started: Instant::now(),
+            ready: AtomicBool::new(false),
+            stats: stats,
+        }
+    }
+
+    /// Marks the node as ready (all servers started) or not ready (shutting down).
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::SeqCst);
+    }
+
+    /// Returns true if the node is ready to serve requests.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    /// Returns current health status.
+    pub fn status(&self) -> HealthStatus {
+        HealthStatus {
+            ready: self.is_ready(),
+            uptime: self.started.elapsed().as_secs(),
+            version: version(),
+            stats: RpcStatsInfo::from(&*self.stats),
+        }
+    }
+}
+
+/// Answers `GET /health` (liveness, always `200`) and `GET /ready`
+/// (`200` if ready, `503` otherwise) before requests reach the JSON-RPC handler.
+pub struct HealthMiddleware {
+    health: Arc<Health>,
+}
+
+impl HealthMiddleware {
+    /// Creates new `HealthMiddleware`.
+    pub fn new(health: Arc<Health>) -> Self {
+        HealthMiddleware { health: health }
+    }
+
+    fn respond(&self, code: hyper::StatusCode) -> RequestMiddlewareAction {
+        let status = self.health.status();
+        let response = Response {
+            code: code,
+            content_type: hyper::header::ContentType::json(),
+            content: serde_json::to_string(&status).expect("HealthStatus serialization is infallible; qed"),
+        };
+
+        // probes usually address the node by IP, which is not in the allowed hosts.
+        RequestMiddlewareAction::Respond {
+            should_validate_hosts: false,
+            handler: future::ok(response.into()).boxed(),
+        }
+    }
+}
+
+impl RequestMiddleware for HealthMiddleware {
+    fn on_request(&self, request: &hyper::server::Request) -> RequestMiddlewareAction {
+        if *request.method() != hyper::Method::Get {
+            return RequestMiddlewareAction::Proceed { should_continue_on_invalid_cors: false };
+        }
+
+        match request.path() {
+            "/health" => self.respond(hyper::StatusCode::Ok),
+            "/ready" if self.health.is_ready() => self.respond(hyper::StatusCode::Ok),
+            "/ready" => self.respond(hyper::StatusCode::ServiceUnavailable),
+            _ => RequestMiddlewareAction::Proceed { should_continue_on_invalid_cors: false },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use util::informant::RpcStats;
+    use super::Health;
+
+    #[test]
+    fn should_report_readiness() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+        let health = Health::new(stats.clone());
+        stats.open_session();
+        assert!(!health.status().ready);
+
+        // when
+        health.set_ready(true);
+
+        // then
+        let status = health.status();
+        assert!(status.ready);
+        assert_eq!(status.stats.sessions, 1);
+
+        // when
+        health.set_ready(false);
+
+        // then
+        assert!(!health.is_ready());
+    }
+}
@@ -17,5 +17,15 @@
 //! Ethereum rpc interface implementation.
 
 mod rpc;
+mod web3;
+mod net;
+mod pubsub;
+mod parity;
+mod parity_set;
 
 pub use self::rpc::RpcClient;
+pub use self::web3::Web3Client;
+pub use self::net::NetClient;
+pub use self::pubsub::PubSubClient;
+pub use self::parity::ParityClient;
+pub use self::parity_set::ParitySetClient;
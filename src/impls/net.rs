@@ -0,0 +1,102 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Net rpc implementation.
+use jsonrpc_core::Error;
+use traits::Net;
+
+/// Net rpc implementation.
+pub struct NetClient {
+    network_id: u64,
+    listening: bool,
+}
+
+impl NetClient {
+    /// Creates new `NetClient`.
+    pub fn new(network_id: u64, listening: bool) -> Self {
+        NetClient {
+            network_id: network_id,
+            listening: listening,
+        }
+    }
+}
+
+impl Net for NetClient {
+    fn version(&self) -> Result<String, Error> {
+        Ok(format!("{}", self.network_id))
+    }
+
+    fn peer_count(&self) -> Result<String, Error> {
+        // the node does not take part in a peer-to-peer network
+        Ok(format!("0x{:x}", 0))
+    }
+
+    fn is_listening(&self) -> Result<bool, Error> {
+        Ok(self.listening)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc_core::MetaIoHandler;
+    use traits::Net;
+    use types::Metadata;
+    use super::NetClient;
+
+    fn io() -> MetaIoHandler<Metadata> {
+        let mut io = MetaIoHandler::default();
+        io.extend_with(NetClient::new(3, true).to_delegate());
+        io
+    }
+
+    #[test]
+    fn should_return_network_id() {
+        // given
+        let io = io();
+        let request = r#"{"jsonrpc": "2.0", "method": "net_version", "params": [], "id": 1}"#;
+
+        // when
+        let response = io.handle_request_sync(request, Metadata::default());
+
+        // then
+        assert_eq!(response, Some(r#"{"jsonrpc":"2.0","result":"3","id":1}"#.to_owned()));
+    }
+
+    #[test]
+    fn should_return_peer_count() {
+        // given
+        let io = io();
+        let request = r#"{"jsonrpc": "2.0", "method": "net_peerCount", "params": [], "id": 1}"#;
+
+        // when
+        let response = io.handle_request_sync(request, Metadata::default());
+
+        // then
+        assert_eq!(response, Some(r#"{"jsonrpc":"2.0","result":"0x0","id":1}"#.to_owned()));
+    }
+
+    #[test]
+    fn should_return_listening_status() {
+        // given
+        let io = io();
+        let request = r#"{"jsonrpc": "2.0", "method": "net_listening", "params": [], "id": 1}"#;
+
+        // when
+        let response = io.handle_request_sync(request, Metadata::default());
+
+        // then
+        assert_eq!(response, Some(r#"{"jsonrpc":"2.0","result":true,"id":1}"#.to_owned()));
+    }
+}
@@ -0,0 +1,52 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Parity-specific rpc implementation.
+use std::sync::Arc;
+use ethcore_logger::RotatingLogger;
+use jsonrpc_core::Error;
+use jsonrpc_macros::Trailing;
+use traits::Parity;
+use types::LogsFilter;
+
+/// Parity implementation.
+pub struct ParityClient {
+    logger: Arc<RotatingLogger>,
+}
+
+impl ParityClient {
+    /// Creates new `ParityClient`.
+    pub fn new(logger: Arc<RotatingLogger>) -> Self {
+        ParityClient { logger: logger }
+    }
+}
+
+impl Parity for ParityClient {
+    fn dev_logs(&self, filter: Trailing<LogsFilter>) -> Result<Vec<String>, Error> {
+        let filter = filter.unwrap_or_default();
+        let level = filter.level().map_err(Error::invalid_params)?;
+        let logs = self.logger.logs();
+        Ok(logs.iter()
+            .filter(|entry| filter.matches(level, entry))
+            .skip(filter.offset.unwrap_or(0))
+            .take(filter.limit.unwrap_or(usize::max_value()))
+            .map(|entry| entry.line.clone())
+            .collect())
+    }
+
+    fn dev_logs_levels(&self) -> Result<String, Error> {
+        Ok(self.logger.levels())
+    }
+}
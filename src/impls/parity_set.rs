@@ -0,0 +1,53 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Parity-specific rpc interface for operations altering the settings.
+use std::sync::Arc;
+use ethcore_logger;
+use jsonrpc_core::Error;
+use traits::ParitySet;
+use util::response_cache::ResponseCache;
+
+/// Parity-specific rpc interface for operations altering the settings.
+#[derive(Default)]
+pub struct ParitySetClient {
+    cache: Option<Arc<ResponseCache>>,
+}
+
+impl ParitySetClient {
+    /// Creates new `ParitySetClient`.
+    pub fn new(cache: Option<Arc<ResponseCache>>) -> Self {
+        ParitySetClient { cache: cache }
+    }
+}
+
+impl ParitySet for ParitySetClient {
+    fn set_log_level(&self, levels: String) -> Result<bool, Error> {
+        ethcore_logger::set_log_levels(&levels).map_err(Error::invalid_params)?;
+        info!("Log levels changed to: {}", levels);
+        Ok(true)
+    }
+
+    fn flush_cache(&self) -> Result<bool, Error> {
+        match self.cache {
+            Some(ref cache) => {
+                cache.flush();
+                info!("Response cache flushed.");
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! PubSub implementation.
+
+use std::sync::Arc;
+use futures::{future, Future};
+use jsonrpc_core::{Error, Params, Value, BoxFuture};
+use jsonrpc_macros::IoDelegate;
+use jsonrpc_macros::pubsub::Subscriber;
+use jsonrpc_pubsub::SubscriptionId;
+use traits::PubSub;
+use types::Metadata;
+use util::subscription_manager::{SubscriptionManager, Topic};
+
+/// PubSub implementation.
+pub struct PubSubClient {
+    manager: Arc<SubscriptionManager>,
+}
+
+impl PubSubClient {
+    /// Creates new `PubSubClient`.
+    pub fn new(manager: Arc<SubscriptionManager>) -> Self {
+        PubSubClient { manager: manager }
+    }
+
+    /// Creates IO delegate for this client.
+    /// `pubsub_unsubscribe` is served with request metadata,
+    /// so that subscriptions can only be cancelled within the session that created them.
+    pub fn into_delegate(self) -> IoDelegate<Self, Metadata> {
+        let mut delegate = self.to_delegate();
+        delegate.add_method_with_meta("pubsub_unsubscribe", |client: &Self, params, meta: Metadata| {
+            client.unsubscribe_in_session(params, meta)
+        });
+        delegate
+    }
+
+    fn unsubscribe_in_session(&self, params: Params, meta: Metadata) -> BoxFuture<Value, Error> {
+        let id = match params {
+            Params::Array(ref params) if params.len() == 1 => SubscriptionId::parse_value(&params[0]),
+            _ => None,
+        };
+
+        match (meta.session, id) {
+            (Some(session), Some(id)) => future::ok(Value::Bool(self.manager.remove(&session, &id))).boxed(),
+            (Some(_), None) => future::err(Error::invalid_params("Expected subscription id.")).boxed(),
+            (None, _) => future::err(Error::invalid_request()).boxed(),
+        }
+    }
+}
+
+impl PubSub for PubSubClient {
+    type Metadata = Metadata;
+
+    fn subscribe(&self, meta: Metadata, subscriber: Subscriber<Value>, topic: String) {
+        let session = match meta.session {
+            Some(session) => session,
+            None => {
+                let _ = subscriber.reject(Error::invalid_request());
+                return;
+            }
+        };
+
+        match topic.parse::<Topic>() {
+            Ok(topic) => self.manager.add(session, topic, subscriber),
+            Err(e) => {
+                let _ = subscriber.reject(Error::invalid_params(e));
+            }
+        }
+    }
+
+    /// Only called when the session closes (see `into_delegate`).
+    /// Subscriptions of closed sessions are already removed by the manager.
+    fn unsubscribe(&self, _id: SubscriptionId) -> BoxFuture<bool, Error> {
+        future::ok(true).boxed()
+    }
+}
@@ -16,24 +16,29 @@
 
 //! RPC generic methods implementation.
 use std::collections::BTreeMap;
+use std::sync::Arc;
 use jsonrpc_core::Error;
+use parking_lot::RwLock;
 use traits::Rpc;
+use types::DiscoverDocument;
 
 /// RPC generic methods implementation.
 pub struct RpcClient {
     modules: BTreeMap<String, String>,
     valid_apis: Vec<String>,
+    document: Arc<RwLock<DiscoverDocument>>,
 }
 
 impl RpcClient {
     /// Creates new `RpcClient`.
-    pub fn new(modules: BTreeMap<String, String>) -> Self {
+    pub fn new(modules: BTreeMap<String, String>, document: Arc<RwLock<DiscoverDocument>>) -> Self {
         // geth 1.3.6 fails upon receiving unknown api
         let valid_apis = vec!["web3", "eth", "net", "personal", "rpc"];
 
         RpcClient {
             modules: modules,
             valid_apis: valid_apis.into_iter().map(|x| x.to_owned()).collect(),
+            document: document,
         }
     }
 }
@@ -61,4 +66,8 @@ impl Rpc for RpcClient {
 
         Ok(modules)
     }
+
+    fn discover(&self) -> Result<DiscoverDocument, Error> {
+        Ok(self.document.read().clone())
+    }
 }
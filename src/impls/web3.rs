@@ -0,0 +1,107 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Web3 rpc implementation.
+use jsonrpc_core::Error;
+use sha3::sha3_256;
+use traits::Web3;
+use types::{H256, Bytes};
+use util::misc::version;
+
+/// Web3 rpc implementation.
+#[derive(Default)]
+pub struct Web3Client;
+
+impl Web3Client {
+    /// Creates new `Web3Client`.
+    pub fn new() -> Self {
+        Web3Client
+    }
+}
+
+/// Computes Keccak-256 of given data.
+fn keccak(data: &[u8]) -> H256 {
+    let mut hash = [0u8; 32];
+    unsafe {
+        sha3_256(hash.as_mut_ptr(), hash.len(), data.as_ptr(), data.len());
+    }
+    H256(hash)
+}
+
+impl Web3 for Web3Client {
+    fn client_version(&self) -> Result<String, Error> {
+        Ok(version())
+    }
+
+    fn sha3(&self, data: Bytes) -> Result<H256, Error> {
+        Ok(keccak(&data.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc_core::MetaIoHandler;
+    use traits::Web3;
+    use types::Metadata;
+    use util::misc::version;
+    use super::Web3Client;
+
+    fn io() -> MetaIoHandler<Metadata> {
+        let mut io = MetaIoHandler::default();
+        io.extend_with(Web3Client::new().to_delegate());
+        io
+    }
+
+    #[test]
+    fn should_return_client_version() {
+        // given
+        let io = io();
+        let request = r#"{"jsonrpc": "2.0", "method": "web3_clientVersion", "params": [], "id": 1}"#;
+
+        // when
+        let response = io.handle_request_sync(request, Metadata::default());
+
+        // then
+        let expected = format!(r#"{{"jsonrpc":"2.0","result":"{}","id":1}}"#, version());
+        assert_eq!(response, Some(expected));
+    }
+
+    #[test]
+    fn should_return_keccak_of_data() {
+        // given
+        let io = io();
+        let request = r#"{"jsonrpc": "2.0", "method": "web3_sha3", "params": ["0x00"], "id": 1}"#;
+
+        // when
+        let response = io.handle_request_sync(request, Metadata::default());
+
+        // then
+        let expected = r#"{"jsonrpc":"2.0","result":"0xbc36789e7a1e281436464229828f817d6612f7b477d66591ff96a9e064bcc98a","id":1}"#;
+        assert_eq!(response, Some(expected.to_owned()));
+    }
+
+    #[test]
+    fn should_reject_invalid_data() {
+        // given
+        let io = io();
+        let request = r#"{"jsonrpc": "2.0", "method": "web3_sha3", "params": ["0x0"], "id": 1}"#;
+
+        // when
+        let response = io.handle_request_sync(request, Metadata::default()).unwrap();
+
+        // then
+        assert!(response.contains(r#""code":-32602"#), "{}", response);
+    }
+}
@@ -16,22 +16,23 @@
 
 extern crate ansi_term;
 use self::ansi_term::Colour::{Green, Cyan, Blue};
+use self::ansi_term::Style;
 
 use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, AtomicBool};
+use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering as AtomicOrdering};
 use std::time::{Instant, Duration};
-use io::TimerToken;
-use parking_lot::{RwLock, Mutex};
+use io::{IoContext, IoHandler, TimerToken};
+use parking_lot::RwLock;
 use number_prefix::{binary_prefix, Standalone, Prefixed};
 use util::informant::RpcStats;
 
 pub struct Informant {
     last_tick: RwLock<Instant>,
+    interval: Duration,
     with_color: bool,
     rpc_stats: Option<Arc<RpcStats>>,
-    last_import: Mutex<Instant>,
+    last_requests: AtomicUsize,
     skipped: AtomicUsize,
-    skipped_txs: AtomicUsize,
     in_shutdown: AtomicBool,
 }
 
@@ -55,24 +56,156 @@ impl MillisecondDuration for Duration {
     }
 }
 
+/// Returns resident memory of this process.
+#[cfg(target_os = "linux")]
+fn process_memory() -> Option<usize> {
+    use std::fs::File;
+    use std::io::Read;
+    use libc;
+
+    let mut statm = String::new();
+    if File::open("/proc/self/statm").and_then(|mut f| f.read_to_string(&mut statm)).is_err() {
+        return None;
+    }
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    statm.split_whitespace()
+        .nth(1)
+        .and_then(|pages| pages.parse::<usize>().ok())
+        .map(|pages| pages * page_size)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_memory() -> Option<usize> {
+    None
+}
+
 impl Informant {
-    /// Make a new instance potentially `with_color` output.
-    pub fn new(rpc_stats: Option<Arc<RpcStats>>, with_color: bool) -> Self {
+    /// Make a new instance potentially `with_color` output, reporting every `interval`.
+    pub fn new(rpc_stats: Option<Arc<RpcStats>>, with_color: bool, interval: Duration) -> Self {
         Informant {
             last_tick: RwLock::new(Instant::now()),
+            interval: interval,
             with_color: with_color,
             rpc_stats: rpc_stats,
-            last_import: Mutex::new(Instant::now()),
+            last_requests: AtomicUsize::new(0),
             skipped: AtomicUsize::new(0),
-            skipped_txs: AtomicUsize::new(0),
             in_shutdown: AtomicBool::new(false),
         }
     }
 
     /// Signal that we're shutting down; no more output necessary.
     pub fn shutdown(&self) {
-        self.in_shutdown.store(true, ::std::sync::atomic::Ordering::SeqCst);
+        self.in_shutdown.store(true, AtomicOrdering::SeqCst);
+    }
+
+    /// Logs status line. Returns false if nothing was printed,
+    /// because there was no activity since last tick or we're shutting down.
+    pub fn tick(&self) -> bool {
+        if self.in_shutdown.load(AtomicOrdering::SeqCst) {
+            return false;
+        }
+
+        let rpc_stats = match self.rpc_stats {
+            Some(ref rpc_stats) => rpc_stats,
+            None => return false,
+        };
+
+        let requests = rpc_stats.requests_total();
+        let last_requests = self.last_requests.swap(requests, AtomicOrdering::SeqCst);
+        let elapsed = {
+            let mut last_tick = self.last_tick.write();
+            let elapsed = last_tick.elapsed();
+            *last_tick = Instant::now();
+            elapsed
+        };
+
+        if requests == last_requests {
+            self.skipped.fetch_add(1, AtomicOrdering::SeqCst);
+            return false;
+        }
+
+        let skipped = self.skipped.swap(0, AtomicOrdering::SeqCst);
+        trace!(target: "informant", "Reporting after {} idle ticks", skipped);
+
+        let paint = |c: Style, t: String| match self.with_color {
+            true => format!("{}", c.paint(t)),
+            false => t,
+        };
+        let rate = (requests - last_requests) as u64 * 1000 / ::std::cmp::max(elapsed.as_milliseconds(), 1);
+
+        info!(target: "informant",
+              "RPC: {} req/s, {} sessions, {} µs roundtrip{}",
+              paint(Cyan.bold(), format!("{:3}", rate)),
+              paint(Green.bold(), format!("{:2}", rpc_stats.sessions())),
+              paint(Blue.bold(), format!("{:4}", rpc_stats.approximated_roundtrip())),
+              process_memory()
+                  .map(|memory| format!(", {} memory", format_bytes(memory)))
+                  .unwrap_or_default());
+
+        true
     }
 }
 
 const INFO_TIMER: TimerToken = 0;
+
+impl IoHandler<()> for Informant {
+    fn initialize(&self, io: &IoContext<()>) {
+        io.register_timer(INFO_TIMER, self.interval.as_milliseconds()).expect("Error registering timer");
+    }
+
+    fn timeout(&self, _io: &IoContext<()>, timer: TimerToken) {
+        if timer == INFO_TIMER {
+            self.tick();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use util::informant::RpcStats;
+    use super::{Informant, format_bytes};
+
+    #[test]
+    fn should_format_bytes() {
+        assert_eq!(format_bytes(512), "512 bytes");
+        assert_eq!(format_bytes(2048), "2 KiB");
+    }
+
+    #[test]
+    fn should_report_only_when_active() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+        let informant = Informant::new(Some(stats.clone()), false, Duration::from_secs(5));
+
+        // when idle
+        let idle = informant.tick();
+        stats.count_request();
+        let active = informant.tick();
+        stats.count_request();
+        informant.shutdown();
+        let shutdown = informant.tick();
+
+        // then
+        assert!(!idle);
+        assert!(active);
+        assert!(!shutdown);
+    }
+
+    #[test]
+    fn should_restart_rate_window_on_idle_ticks() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+        let informant = Informant::new(Some(stats), false, Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(50));
+
+        // when
+        let idle = informant.tick();
+
+        // then
+        assert!(!idle);
+        assert!(informant.last_tick.read().elapsed() < Duration::from_millis(50));
+    }
+}
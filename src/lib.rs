@@ -0,0 +1,83 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Toy JSON-RPC node: RPC servers over HTTP, WebSockets and IPC with pluggable API modules.
+
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate log as rlog;
+extern crate env_logger;
+extern crate arrayvec;
+extern crate parking_lot;
+extern crate ansi_term;
+extern crate futures;
+extern crate order_stat;
+extern crate rustc_serialize;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate rand;
+extern crate bigint;
+extern crate libc;
+extern crate lru_cache;
+#[macro_use]
+extern crate heapsize;
+extern crate jsonrpc_core;
+extern crate jsonrpc_http_server;
+extern crate jsonrpc_ws_server;
+extern crate jsonrpc_ipc_server;
+extern crate jsonrpc_pubsub;
+#[macro_use]
+extern crate jsonrpc_macros;
+extern crate ethcore_io as io;
+extern crate ethcore_logger;
+extern crate sha3;
+extern crate fetch;
+extern crate ethcore_util;
+extern crate number_prefix;
+extern crate app_dirs;
+extern crate parity_reactor;
+extern crate serde_json;
+extern crate target_info;
+extern crate futures_cpupool;
+extern crate openssl;
+extern crate tokio_core;
+extern crate tokio_io;
+extern crate tokio_openssl;
+
+pub mod util;
+pub mod rpc;
+pub mod types;
+pub mod api;
+pub mod traits;
+pub mod impls;
+pub mod informant;
+pub mod helpers;
+pub mod dir;
+pub mod user_defaults;
+pub mod metrics;
+pub mod tokens;
+pub mod replay;
+pub mod client;
+pub mod tls;
+pub mod health;
+pub mod node;
+
+pub use api::apis::{Api, ApiSet, ApiSafety, Module};
+pub use api::discovery::MethodDoc;
+pub use node::{Node, NodeBuilder};
+pub use rpc::{HttpConfiguration, WsConfiguration, IpcConfiguration};
+pub use types::ContentDescriptor;
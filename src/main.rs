@@ -1,49 +1,20 @@
 #[macro_use]
-extern crate lazy_static;
-#[macro_use]
 extern crate log as rlog;
-extern crate env_logger;
-extern crate arrayvec;
-extern crate parking_lot;
-extern crate ansi_term;
-extern crate futures;
-extern crate order_stat;
-extern crate rustc_serialize;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
-extern crate rand;
-extern crate bigint;
+extern crate parking_lot;
 extern crate libc;
-#[macro_use]
-extern crate heapsize;
-extern crate jsonrpc_core;
-extern crate jsonrpc_http_server;
-#[macro_use]
-extern crate jsonrpc_macros;
 extern crate ethcore_io as io;
 extern crate ctrlc;
 extern crate fdlimit;
 extern crate ethcore_logger;
-extern crate number_prefix;
-extern crate app_dirs;
-extern crate parity_reactor;
-extern crate serde_json;
 #[macro_use]
 extern crate clap;
-extern crate target_info;
-extern crate futures_cpupool;
+extern crate toml;
+extern crate toyrpc;
 
-mod util;
-mod rpc;
-mod types;
-mod api;
-mod traits;
-mod impls;
-mod informant;
-mod helpers;
-mod dir;
-mod user_defaults;
+mod config_file;
 mod configuration;
 mod run;
 
@@ -51,6 +22,7 @@ use std::{process, env};
 use std::io::{self as stdio, Write};
 use configuration::{Cmd, Execute, Configuration};
 use ethcore_logger::setup_log;
+use toyrpc::{client, replay, tokens};
 
 enum PostExecutionAction {
     Print(String),
@@ -65,7 +37,7 @@ fn execute(command: Execute, can_restart: bool) -> Result<PostExecutionAction, S
 
     match command.cmd {
         Cmd::Run(run_cmd) => {
-            let (restart, spec_name) = run::execute(run_cmd, can_restart, logger)?;
+            let (restart, spec_name) = run::execute(run_cmd, logger, can_restart)?;
             Ok(if restart {
                 PostExecutionAction::Restart(spec_name)
             } else {
@@ -73,6 +45,12 @@ fn execute(command: Execute, can_restart: bool) -> Result<PostExecutionAction, S
             })
         }
         Cmd::Version => Ok(PostExecutionAction::Quit),
+        Cmd::DumpConfig(config) => Ok(PostExecutionAction::Print(config)),
+        Cmd::Tokens(tokens_cmd) => tokens::execute(tokens_cmd).map(PostExecutionAction::Print),
+        Cmd::Replay(replay_cmd) => replay::execute(replay_cmd, logger).map(PostExecutionAction::Print),
+        Cmd::Client(client_cmd) => {
+            client::execute(client_cmd).map(|output| output.map_or(PostExecutionAction::Quit, PostExecutionAction::Print))
+        }
     }
 }
 
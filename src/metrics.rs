@@ -0,0 +1,262 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Prometheus metrics endpoint.
+
+use std::fmt::Write as FmtWrite;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use util::informant::{RpcStats, CallStats};
+
+const QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MetricsConfiguration {
+    pub enabled: bool,
+    pub interface: String,
+    pub port: u16,
+}
+
+impl Default for MetricsConfiguration {
+    fn default() -> Self {
+        MetricsConfiguration {
+            enabled: false,
+            interface: "127.0.0.1".into(),
+            port: 9545,
+        }
+    }
+}
+
+/// Metrics HTTP listener. Stops when dropped.
+pub struct MetricsServer {
+    addr: SocketAddr,
+    done: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Returns address the server is listening on.
+    pub fn addr(&self) -> &SocketAddr {
+        &self.addr
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+        // wake up the blocking `accept`
+        let _ = TcpStream::connect(&self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+pub fn new_metrics(conf: MetricsConfiguration, stats: Arc<RpcStats>) -> Result<Option<MetricsServer>, String> {
+    if !conf.enabled {
+        return Ok(None);
+    }
+
+    let url = format!("{}:{}", conf.interface, conf.port);
+    let addr: SocketAddr = url.parse().map_err(|_| format!("Invalid metrics listen host/port given: {}", url))?;
+    let listener = match TcpListener::bind(&addr) {
+        Ok(listener) => listener,
+        Err(ref err) if err.kind() == io::ErrorKind::AddrInUse => {
+            return Err(format!("Metrics address {} is already in use, change the address using the \
+                                --metrics-port and --metrics-interface options.",
+                               url))
+        }
+        Err(e) => return Err(format!("Metrics error: {:?}", e)),
+    };
+    let addr = listener.local_addr().map_err(|e| format!("Metrics error: {:?}", e))?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let thread_done = done.clone();
+    let thread = thread::Builder::new()
+        .name("metrics".into())
+        .spawn(move || for stream in listener.incoming() {
+            if thread_done.load(Ordering::SeqCst) {
+                break;
+            }
+
+            if let Ok(stream) = stream {
+                if let Err(e) = handle(stream, &stats) {
+                    debug!(target: "metrics", "Error serving metrics: {:?}", e);
+                }
+            }
+        })
+        .map_err(|e| format!("Metrics error: {:?}", e))?;
+
+    Ok(Some(MetricsServer {
+        addr: addr,
+        done: done,
+        thread: Some(thread),
+    }))
+}
+
+fn handle(mut stream: TcpStream, stats: &RpcStats) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut request_line = String::new();
+    {
+        let mut reader = BufReader::new(&stream);
+        reader.read_line(&mut request_line)?;
+        // skip headers
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render(stats)),
+        _ => ("404 Not Found", "Not Found\n".to_owned()),
+    };
+
+    write!(stream,
+           "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           status,
+           body.len(),
+           body)?;
+    stream.flush()
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn seconds(microseconds: u64) -> f64 {
+    microseconds as f64 / 1_000_000f64
+}
+
+fn render_calls(out: &mut String, prefix: &str, label: &str, calls: &[(String, CallStats)]) {
+    let _ = writeln!(out, "# HELP {}_calls_total Number of JSON-RPC calls per {}.", prefix, label);
+    let _ = writeln!(out, "# TYPE {}_calls_total counter", prefix);
+    for &(ref name, ref stats) in calls {
+        let _ = writeln!(out, "{}_calls_total{{{}=\"{}\"}} {}", prefix, label, escape(name), stats.calls);
+    }
+
+    let _ = writeln!(out, "# HELP {}_errors_total Number of failed JSON-RPC calls per {}.", prefix, label);
+    let _ = writeln!(out, "# TYPE {}_errors_total counter", prefix);
+    for &(ref name, ref stats) in calls {
+        let _ = writeln!(out, "{}_errors_total{{{}=\"{}\"}} {}", prefix, label, escape(name), stats.errors);
+    }
+
+    let _ = writeln!(out, "# HELP {}_duration_seconds JSON-RPC calls latency per {}.", prefix, label);
+    let _ = writeln!(out, "# TYPE {}_duration_seconds histogram", prefix);
+    for &(ref name, ref stats) in calls {
+        let name = escape(name);
+        for (bound, count) in stats.latency.buckets() {
+            let _ = writeln!(out, "{}_duration_seconds_bucket{{{}=\"{}\",le=\"{}\"}} {}", prefix, label, name, seconds(bound as u64), count);
+        }
+        let _ = writeln!(out, "{}_duration_seconds_bucket{{{}=\"{}\",le=\"+Inf\"}} {}", prefix, label, name, stats.latency.count());
+        let _ = writeln!(out, "{}_duration_seconds_sum{{{}=\"{}\"}} {}", prefix, label, name, seconds(stats.latency.sum()));
+        let _ = writeln!(out, "{}_duration_seconds_count{{{}=\"{}\"}} {}", prefix, label, name, stats.latency.count());
+    }
+
+    let _ = writeln!(out, "# HELP {}_duration_quantile_seconds Estimated JSON-RPC calls latency quantiles per {}.", prefix, label);
+    let _ = writeln!(out, "# TYPE {}_duration_quantile_seconds gauge", prefix);
+    for &(ref name, ref stats) in calls {
+        let name = escape(name);
+        for q in &QUANTILES {
+            let _ = writeln!(out, "{}_duration_quantile_seconds{{{}=\"{}\",quantile=\"{}\"}} {}", prefix, label, name, q, seconds(stats.latency.quantile(*q) as u64));
+        }
+    }
+}
+
+/// Renders stats in Prometheus text exposition format.
+pub fn render(stats: &RpcStats) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# HELP toyrpc_active_sessions Number of open sessions.");
+    let _ = writeln!(out, "# TYPE toyrpc_active_sessions gauge");
+    let _ = writeln!(out, "toyrpc_active_sessions {}", stats.sessions());
+    let _ = writeln!(out, "# HELP toyrpc_requests_rate Number of requests per second.");
+    let _ = writeln!(out, "# TYPE toyrpc_requests_rate gauge");
+    let _ = writeln!(out, "toyrpc_requests_rate {}", stats.requests_rate());
+    let _ = writeln!(out, "# HELP toyrpc_rate_limited_total Number of requests rejected by the rate limiter.");
+    let _ = writeln!(out, "# TYPE toyrpc_rate_limited_total counter");
+    let _ = writeln!(out, "toyrpc_rate_limited_total {}", stats.rate_limited());
+    let _ = writeln!(out, "# HELP toyrpc_timeouts_total Number of requests cancelled after their deadline.");
+    let _ = writeln!(out, "# TYPE toyrpc_timeouts_total counter");
+    let _ = writeln!(out, "toyrpc_timeouts_total {}", stats.timeouts());
+    let _ = writeln!(out, "# HELP toyrpc_cache_hits_total Number of calls answered from the response cache.");
+    let _ = writeln!(out, "# TYPE toyrpc_cache_hits_total counter");
+    let _ = writeln!(out, "toyrpc_cache_hits_total {}", stats.cache_hits());
+    let _ = writeln!(out, "# HELP toyrpc_cache_misses_total Number of cacheable calls not found in the response cache.");
+    let _ = writeln!(out, "# TYPE toyrpc_cache_misses_total counter");
+    let _ = writeln!(out, "toyrpc_cache_misses_total {}", stats.cache_misses());
+
+    let methods = stats.methods().into_iter().collect::<Vec<_>>();
+    render_calls(&mut out, "toyrpc_method", "method", &methods);
+    let origins = stats.origins().into_iter().collect::<Vec<_>>();
+    render_calls(&mut out, "toyrpc_origin", "origin", &origins);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use util::informant::RpcStats;
+    use super::{new_metrics, render, MetricsConfiguration};
+
+    #[test]
+    fn should_render_prometheus_format() {
+        // given
+        let stats = RpcStats::default();
+        stats.add_call("web3_sha3", "rpc", 80, false);
+        stats.add_call("web3_sha3", "rpc", 900, true);
+
+        // when
+        let res = render(&stats);
+
+        // then
+        assert!(res.contains("toyrpc_method_calls_total{method=\"web3_sha3\"} 2\n"));
+        assert!(res.contains("toyrpc_method_errors_total{method=\"web3_sha3\"} 1\n"));
+        assert!(res.contains("toyrpc_method_duration_seconds_bucket{method=\"web3_sha3\",le=\"0.0001\"} 1\n"));
+        assert!(res.contains("toyrpc_method_duration_seconds_count{method=\"web3_sha3\"} 2\n"));
+        assert!(res.contains("toyrpc_origin_calls_total{origin=\"rpc\"} 2\n"));
+    }
+
+    #[test]
+    fn should_serve_metrics_over_http() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+        stats.add_call("rpc_modules", "ws", 100, false);
+        let conf = MetricsConfiguration {
+            enabled: true,
+            port: 0,
+            ..Default::default()
+        };
+        let server = new_metrics(conf, stats).unwrap().unwrap();
+
+        // when
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        // then
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("toyrpc_method_calls_total{method=\"rpc_modules\"} 1\n"));
+    }
+}
@@ -0,0 +1,505 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Embeddable node: RPC servers with built-in and custom API modules.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use ansi_term::Colour;
+use ethcore_logger::RotatingLogger;
+use io::IoService;
+use jsonrpc_core::RemoteProcedure;
+use parity_reactor::EventLoop;
+use parking_lot::{Mutex, RwLock};
+use api::apis::{self, ApiSafety, Module};
+use dir::Directories;
+use health::Health;
+use informant::Informant;
+use metrics::{self, MetricsConfiguration, MetricsServer};
+use rpc::{self, HttpConfiguration, HttpServer, WsConfiguration, WsServer, IpcConfiguration, IpcServer};
+use tokens::TokenStore;
+use types::Metadata;
+use util::capture::Capture;
+use util::informant::{ActivityNotifier, Builder, ClientNotifier, RpcStats};
+use util::misc::version;
+use util::rate_limit::{RateLimiter, RateLimitConfiguration};
+use util::subscription_manager::SubscriptionManager;
+use util::timeouts::TimeoutConfiguration;
+use util::upstream::{Upstreams, UpstreamConfiguration};
+use util::response_cache::{CacheConfiguration, ResponseCache};
+
+/// Configures and creates a `Node`.
+///
+/// ```no_run
+/// #[macro_use]
+/// extern crate jsonrpc_macros;
+/// extern crate jsonrpc_core;
+/// extern crate toyrpc;
+///
+/// use jsonrpc_core::Error;
+/// use toyrpc::{ApiSafety, HttpConfiguration, NodeBuilder};
+///
+/// build_rpc_trait! {
+/// 	pub trait Hello {
+/// 		#[rpc(name = "hello_world")]
+/// 		fn world(&self) -> Result<String, Error>;
+/// 	}
+/// }
+///
+/// struct HelloClient;
+/// impl Hello for HelloClient {
+/// 	fn world(&self) -> Result<String, Error> {
+/// 		Ok("Hello World!".into())
+/// 	}
+/// }
+///
+/// fn main() {
+/// 	let mut node = NodeBuilder::new(HttpConfiguration::default())
+/// 		.module("hello", "1.0", ApiSafety::Safe, HelloClient.to_delegate())
+/// 		.build();
+/// 	node.start().unwrap();
+/// 	node.stop();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct NodeBuilder {
+    http_conf: HttpConfiguration,
+    ws_conf: WsConfiguration,
+    ipc_conf: IpcConfiguration,
+    metrics_conf: MetricsConfiguration,
+    rate_limit_conf: RateLimitConfiguration,
+    timeout_conf: TimeoutConfiguration,
+    upstream_conf: UpstreamConfiguration,
+    cache_conf: CacheConfiguration,
+    logger: Option<Arc<RotatingLogger>>,
+    color: bool,
+    dirs: Directories,
+    informant_interval: Duration,
+    shutdown_timeout: Duration,
+    network_id: u64,
+    capture: bool,
+    modules: Vec<Module>,
+}
+
+impl NodeBuilder {
+    /// Creates new builder serving JSON-RPC over HTTP. WebSockets and IPC are disabled.
+    pub fn new(http_conf: HttpConfiguration) -> Self {
+        NodeBuilder {
+            http_conf: http_conf,
+            ws_conf: WsConfiguration { enabled: false, ..Default::default() },
+            ipc_conf: IpcConfiguration { enabled: false, ..Default::default() },
+            metrics_conf: MetricsConfiguration::default(),
+            rate_limit_conf: RateLimitConfiguration::default(),
+            timeout_conf: TimeoutConfiguration::default(),
+            upstream_conf: UpstreamConfiguration::default(),
+            cache_conf: CacheConfiguration::default(),
+            logger: None,
+            color: !cfg!(windows),
+            dirs: Directories::default(),
+            informant_interval: Duration::from_secs(5),
+            shutdown_timeout: Duration::from_secs(30),
+            network_id: 1,
+            capture: false,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Sets logger whose recent entries are served by `parity_devLogs`.
+    /// The global logger is left to the caller, e.g. set up with `ethcore_logger::setup_log`.
+    pub fn logger(mut self, logger: Arc<RotatingLogger>) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// Sets whether the status line is colored.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets WebSockets server configuration.
+    pub fn ws(mut self, ws_conf: WsConfiguration) -> Self {
+        self.ws_conf = ws_conf;
+        self
+    }
+
+    /// Sets IPC server configuration.
+    pub fn ipc(mut self, ipc_conf: IpcConfiguration) -> Self {
+        self.ipc_conf = ipc_conf;
+        self
+    }
+
+    /// Sets metrics server configuration.
+    pub fn metrics(mut self, metrics_conf: MetricsConfiguration) -> Self {
+        self.metrics_conf = metrics_conf;
+        self
+    }
+
+    /// Sets rate limits.
+    pub fn rate_limit(mut self, rate_limit_conf: RateLimitConfiguration) -> Self {
+        self.rate_limit_conf = rate_limit_conf;
+        self
+    }
+
+    /// Sets request deadlines.
+    pub fn timeouts(mut self, timeout_conf: TimeoutConfiguration) -> Self {
+        self.timeout_conf = timeout_conf;
+        self
+    }
+
+    /// Sets upstream nodes receiving calls of methods not served locally.
+    pub fn upstream(mut self, upstream_conf: UpstreamConfiguration) -> Self {
+        self.upstream_conf = upstream_conf;
+        self
+    }
+
+    /// Sets caching of responses to cacheable methods.
+    pub fn cache(mut self, cache_conf: CacheConfiguration) -> Self {
+        self.cache_conf = cache_conf;
+        self
+    }
+
+    /// Sets data directories.
+    pub fn dirs(mut self, dirs: Directories) -> Self {
+        self.dirs = dirs;
+        self
+    }
+
+    /// Sets how often the status line is logged.
+    pub fn informant_interval(mut self, interval: Duration) -> Self {
+        self.informant_interval = interval;
+        self
+    }
+
+    /// Sets how long `stop` waits for in-flight requests.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Sets network id reported by `net_version`.
+    pub fn network_id(mut self, network_id: u64) -> Self {
+        self.network_id = network_id;
+        self
+    }
+
+    /// Enables recording of processed requests to `capture.ndjson` in the base directory.
+    pub fn capture(mut self, capture: bool) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// Registers module of methods. The module is listed by `rpc_modules` with given version
+    /// and its safety decides which API sets include it. `Node::start` fails if the name is taken
+    /// by a built-in API or another module, API lists can include it using `ApiSet::parse_with`.
+    pub fn module<N, V, D>(self, name: N, version: V, safety: ApiSafety, delegate: D) -> Self
+        where N: Into<String>,
+              V: Into<String>,
+              D: Into<HashMap<String, RemoteProcedure<Metadata>>>
+    {
+        self.with_module(Module::new(name, version, safety, delegate))
+    }
+
+    /// Registers already created module, e.g. one with method descriptions for `rpc_discover`.
+    pub fn with_module(mut self, module: Module) -> Self {
+        self.modules.push(module);
+        self
+    }
+
+    /// Creates the node. Servers are started with `Node::start`.
+    pub fn build(self) -> Node {
+        Node {
+            builder: self,
+            running: None,
+        }
+    }
+}
+
+struct Running {
+    stats: Arc<RpcStats>,
+    health: Arc<Health>,
+    notifier: ClientNotifier,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    timeouts: Arc<RwLock<TimeoutConfiguration>>,
+    upstreams: Option<Arc<Upstreams>>,
+    cache: Option<Arc<ResponseCache>>,
+    shutdown_timeout: Mutex<Duration>,
+    http_server: Option<HttpServer>,
+    ws_server: Option<WsServer>,
+    ipc_server: Option<IpcServer>,
+    metrics_server: Option<MetricsServer>,
+    informant: Arc<Informant>,
+    io_service: IoService<()>,
+    event_loop: EventLoop,
+}
+
+/// Handle to a node created by `NodeBuilder`. Stops the node when dropped.
+pub struct Node {
+    builder: NodeBuilder,
+    running: Option<Running>,
+}
+
+impl Node {
+    /// Returns true if the node is started.
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Starts configured servers.
+    pub fn start(&mut self) -> Result<(), String> {
+        if self.running.is_some() {
+            return Err("Node is already running.".into());
+        }
+
+        let conf = self.builder.clone();
+        if conf.informant_interval == Duration::from_secs(0) {
+            return Err("Informant interval must not be zero.".into());
+        }
+        apis::validate_modules(&conf.modules)?;
+        let logger = conf.logger.unwrap_or_else(|| Arc::new(RotatingLogger::new(String::new())));
+
+        info!("Starting {}", Colour::White.bold().paint(version()));
+
+        // spin up event loop
+        let event_loop = EventLoop::spawn();
+
+        // set up dependencies for rpc servers
+        let rpc_stats = Arc::new(RpcStats::default());
+        let subscriptions = Arc::new(SubscriptionManager::new(rpc_stats.clone()));
+        SubscriptionManager::start(&subscriptions, &event_loop.raw_remote());
+        let cache = ResponseCache::new(conf.cache_conf, rpc_stats.clone()).map(Arc::new);
+        let deps_for_rpc_apis = Arc::new(apis::Dependencies {
+            logger: logger,
+            subscriptions: subscriptions,
+            network_id: conf.network_id,
+            listening: conf.http_conf.enabled || conf.ws_conf.enabled || conf.ipc_conf.enabled,
+            modules: conf.modules,
+            cache: cache.clone(),
+        });
+
+        let tokens = match conf.http_conf.auth || conf.ws_conf.auth {
+            true => Some(Arc::new(TokenStore::new(conf.dirs.tokens_path())?)),
+            false => None,
+        };
+
+        let capture = match conf.capture {
+            true => {
+                let capture = Capture::new(conf.dirs.capture_path())?;
+                info!("Capturing requests to {}", capture.path().display());
+                Some(Arc::new(capture))
+            }
+            false => None,
+        };
+
+        let upstreams = Upstreams::new(conf.upstream_conf)?.map(Arc::new);
+        let health = Arc::new(Health::new(rpc_stats.clone()));
+        let notifier = ClientNotifier::default();
+        let rate_limiter = RateLimiter::new(conf.rate_limit_conf).map(Arc::new);
+        let timeouts = Arc::new(RwLock::new(conf.timeout_conf));
+
+        let dependencies = rpc::Dependencies {
+            apis: deps_for_rpc_apis,
+            remote: event_loop.raw_remote(),
+            stats: rpc_stats.clone(),
+            notifier: notifier.clone(),
+            pool: Some(Builder::new().create()),
+            rate_limiter: rate_limiter.clone(),
+            tokens: tokens,
+            capture: capture,
+            health: health.clone(),
+            timeouts: timeouts.clone(),
+            upstreams: upstreams.clone(),
+        };
+
+        // start rpc servers
+        let http_server = rpc::new_http(conf.http_conf, &dependencies)?;
+        let ws_server = rpc::new_ws(conf.ws_conf, &dependencies)?;
+        let ipc_server = rpc::new_ipc(conf.ipc_conf, &dependencies)?;
+        let metrics_server = metrics::new_metrics(conf.metrics_conf, rpc_stats.clone())?;
+
+        // the informant
+        let informant = Arc::new(Informant::new(Some(rpc_stats.clone()),
+                                                conf.color,
+                                                conf.informant_interval));
+        let io_service = IoService::<()>::start().map_err(|e| format!("Unable to start IO service: {}", e))?;
+        io_service.register_handler(informant.clone())
+            .map_err(|e| format!("Unable to register informant: {}", e))?;
+
+        // all configured servers are up
+        health.set_ready(true);
+
+        self.running = Some(Running {
+            stats: rpc_stats,
+            health: health,
+            notifier: notifier,
+            rate_limiter: rate_limiter,
+            timeouts: timeouts,
+            upstreams: upstreams,
+            cache: cache,
+            shutdown_timeout: Mutex::new(conf.shutdown_timeout),
+            http_server: http_server,
+            ws_server: ws_server,
+            ipc_server: ipc_server,
+            metrics_server: metrics_server,
+            informant: informant,
+            io_service: io_service,
+            event_loop: event_loop,
+        });
+        Ok(())
+    }
+
+    /// Stops accepting requests, lets in-flight requests finish within the shutdown timeout
+    /// and stops the servers. Returns number of requests which were cut off.
+    pub fn stop(&mut self) -> usize {
+        let running = match self.running.take() {
+            Some(running) => running,
+            None => return 0,
+        };
+
+        // reject new requests, let in-flight requests finish.
+        // servers keep running meanwhile, so probes can see the node is not ready.
+        running.health.set_ready(false);
+        running.notifier.shutdown();
+        let timeout = *running.shutdown_timeout.lock();
+        let cut_off = drain(&running.stats, timeout);
+        if cut_off > 0 {
+            warn!("{} in-flight requests did not finish within {}s and were cut off.",
+                  cut_off,
+                  timeout.as_secs());
+        }
+        drop((running.http_server, running.ws_server, running.ipc_server));
+        drop((running.metrics_server, running.event_loop));
+
+        // to make sure timer does not spawn requests while shutdown is in progress
+        running.informant.shutdown();
+        drop(running.io_service);
+        // just Arc is dropping here, to allow other reference release in its default time
+        drop(running.informant);
+
+        cut_off
+    }
+
+    /// Replaces rate limits of the running node.
+    pub fn set_rate_limits(&self, rate_limit_conf: RateLimitConfiguration) -> Result<(), String> {
+        match self.running.as_ref().and_then(|running| running.rate_limiter.as_ref()) {
+            Some(rate_limiter) => {
+                rate_limiter.reconfigure(rate_limit_conf);
+                Ok(())
+            }
+            None if rate_limit_conf == RateLimitConfiguration::default() => Ok(()),
+            None => Err("Rate limiting was disabled at startup, restart to enable it.".into()),
+        }
+    }
+
+    /// Replaces request deadlines of the running node.
+    pub fn set_timeouts(&self, timeout_conf: TimeoutConfiguration) {
+        if let Some(ref running) = self.running {
+            *running.timeouts.write() = timeout_conf;
+        }
+    }
+
+    /// Replaces methods forwarded to upstream nodes. Upstream URLs cannot change at runtime.
+    pub fn set_upstream(&self, upstream_conf: UpstreamConfiguration) -> Result<(), String> {
+        match self.running.as_ref().and_then(|running| running.upstreams.as_ref()) {
+            Some(upstreams) if upstreams.urls() == upstream_conf.urls => {
+                upstreams.set_rules(upstream_conf.allow, upstream_conf.deny);
+                Ok(())
+            }
+            None if upstream_conf.urls.is_empty() => Ok(()),
+            _ => Err("Upstream nodes changed, restart to use them.".into()),
+        }
+    }
+
+    /// Changes memory budget of the response cache, cached responses are removed.
+    pub fn set_cache(&self, cache_conf: CacheConfiguration) -> Result<(), String> {
+        match self.running.as_ref().and_then(|running| running.cache.as_ref()) {
+            Some(cache) if cache_conf.enabled => {
+                cache.resize(cache_conf.size);
+                Ok(())
+            }
+            None if !cache_conf.enabled => Ok(()),
+            _ => Err("Response caching was toggled, restart to apply it.".into()),
+        }
+    }
+
+    /// Changes how long `stop` waits for in-flight requests.
+    pub fn set_shutdown_timeout(&self, timeout: Duration) {
+        if let Some(ref running) = self.running {
+            *running.shutdown_timeout.lock() = timeout;
+        }
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Waits until in-flight requests finish or `timeout` passes.
+/// Returns number of requests still being processed.
+fn drain(stats: &RpcStats, timeout: Duration) -> usize {
+    let deadline = Instant::now() + timeout;
+    while stats.in_flight() > 0 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(50));
+    }
+    stats.in_flight()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use util::informant::{InFlight, RpcStats};
+    use super::drain;
+
+    #[test]
+    fn should_wait_for_in_flight_requests() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+        let request = InFlight::new(stats.clone());
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            drop(request);
+        });
+        let start = Instant::now();
+
+        // when
+        let cut_off = drain(&stats, Duration::from_secs(5));
+
+        // then
+        assert_eq!(cut_off, 0);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn should_cut_off_requests_after_timeout() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+        let _request = InFlight::new(stats.clone());
+        let start = Instant::now();
+
+        // when
+        let cut_off = drain(&stats, Duration::from_millis(100));
+
+        // then
+        assert_eq!(cut_off, 1);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Replay of captured JSON-RPC traffic.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use ethcore_logger::RotatingLogger;
+use jsonrpc_core::{MetaIoHandler, Middleware};
+use serde_json::{self, Value};
+use api::apis::{self, ApiSet};
+use types::{Metadata, Origin};
+use util::capture::{self, CaptureEntry};
+use util::informant::{self, ClientNotifier, RpcStats};
+use util::subscription_manager::SubscriptionManager;
+
+#[derive(Debug, PartialEq)]
+pub struct ReplayCmd {
+    /// Capture file to replay
+    pub file: PathBuf,
+    /// Network id reported by `net_version`
+    pub network_id: u64,
+    /// APIs served over HTTP, used for requests which did not come over WebSockets or IPC
+    pub http_apis: ApiSet,
+    /// APIs served over WebSockets
+    pub ws_apis: ApiSet,
+    /// APIs served over IPC
+    pub ipc_apis: ApiSet,
+}
+
+/// Captured request which now yields a different response.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    /// Position of the entry in the capture file (starting at 1)
+    pub entry: usize,
+    /// Captured request
+    pub request: Value,
+    /// Recorded response
+    pub recorded: Option<Value>,
+    /// Response returned now
+    pub replayed: Option<Value>,
+}
+
+/// Feeds captured requests through handlers selected by their origin
+/// and returns the ones with differing responses.
+pub fn replay<'a, M, F>(handler: F, entries: &[CaptureEntry]) -> Vec<Mismatch>
+    where M: Middleware<Metadata>,
+          F: Fn(&Origin) -> &'a MetaIoHandler<Metadata, M>
+{
+    entries.iter()
+        .enumerate()
+        .filter_map(|(idx, entry)| {
+            let meta = Metadata { origin: entry.origin.clone(), ..Default::default() };
+            let replayed = handler(&entry.origin).handle_request_sync(&entry.request.to_string(), meta)
+                .and_then(|response| serde_json::from_str(&response).ok());
+
+            match replayed == entry.response {
+                true => None,
+                false => {
+                    Some(Mismatch {
+                        entry: idx + 1,
+                        request: entry.request.clone(),
+                        recorded: entry.response.clone(),
+                        replayed: replayed,
+                    })
+                }
+            }
+        })
+        .collect()
+}
+
+fn display(response: &Option<Value>) -> String {
+    response.as_ref().map_or_else(|| "no response".into(), Value::to_string)
+}
+
+/// Replays capture file against APIs configured for the server each request came through.
+/// Fails if any response differs.
+pub fn execute(cmd: ReplayCmd, logger: Arc<RotatingLogger>) -> Result<String, String> {
+    let entries = capture::read(&cmd.file)?;
+
+    let stats = Arc::new(RpcStats::default());
+    let deps = Arc::new(apis::Dependencies {
+        logger: logger,
+        subscriptions: Arc::new(SubscriptionManager::new(stats.clone())),
+        network_id: cmd.network_id,
+        listening: true,
+        modules: Vec::new(),
+        cache: None,
+    });
+    let setup = |apis| {
+        let middleware = informant::Middleware::new(stats.clone(), ClientNotifier::default(), None);
+        apis::setup_apis(middleware, deps.clone(), apis)
+    };
+    let http = setup(cmd.http_apis);
+    let ws = setup(cmd.ws_apis);
+    let ipc = setup(cmd.ipc_apis);
+
+    let mismatches = replay(|origin| match *origin {
+        Origin::Ws(_) => &ws,
+        Origin::Ipc(_) => &ipc,
+        _ => &http,
+    }, &entries);
+    let mut report = format!("Replayed {} requests, {} responses differ.", entries.len(), mismatches.len());
+    for mismatch in &mismatches {
+        report.push_str(&format!("\n\n#{}: {}\n  recorded: {}\n  replayed: {}",
+                                 mismatch.entry,
+                                 mismatch.request,
+                                 display(&mismatch.recorded),
+                                 display(&mismatch.replayed)));
+    }
+
+    match mismatches.is_empty() {
+        true => Ok(report),
+        false => Err(report),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc_core::{MetaIoHandler, Value};
+    use serde_json;
+    use types::{Metadata, Origin};
+    use util::capture::CaptureEntry;
+    use super::replay;
+
+    fn entry(request: &str, response: Option<&str>) -> CaptureEntry {
+        CaptureEntry {
+            timestamp: 0,
+            origin: Origin::Unknown,
+            latency: 0,
+            request: serde_json::from_str(request).unwrap(),
+            response: response.map(|response| serde_json::from_str(response).unwrap()),
+        }
+    }
+
+    #[test]
+    fn should_report_differing_responses() {
+        // given
+        let mut io = MetaIoHandler::<Metadata>::default();
+        io.add_method("hello", |_| Ok(Value::String("world".into())));
+        let entries = vec![
+            entry(r#"{"jsonrpc":"2.0","method":"hello","params":[],"id":1}"#,
+                  Some(r#"{"jsonrpc":"2.0","result":"world","id":1}"#)),
+            entry(r#"{"jsonrpc":"2.0","method":"hello","params":[],"id":2}"#,
+                  Some(r#"{"jsonrpc":"2.0","result":"moon","id":2}"#)),
+            entry(r#"{"jsonrpc":"2.0","method":"hello","params":[]}"#, None),
+        ];
+
+        // when
+        let mismatches = replay(|_| &io, &entries);
+
+        // then
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].entry, 2);
+        let replayed: Value = serde_json::from_str(r#"{"jsonrpc":"2.0","result":"world","id":2}"#).unwrap();
+        assert_eq!(mismatches[0].replayed, Some(replayed));
+    }
+}
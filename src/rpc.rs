@@ -1,20 +1,39 @@
 use jsonrpc_core;
 use jsonrpc_core::MetaIoHandler;
-use jsonrpc_http_server::{ServerBuilder, MetaExtractor,
+use jsonrpc_http_server;
+use jsonrpc_http_server::{ServerBuilder, MetaExtractor, RequestMiddleware, RequestMiddlewareAction, Response,
                           AccessControlAllowOrigin, Host, DomainsValidation};
-use types::{Origin, Metadata};
-use util::informant::{Middleware, RpcStats, CpuPool};
+use futures::{future, Future};
+use jsonrpc_ws_server as ws;
+use jsonrpc_ipc_server as ipc;
+use jsonrpc_pubsub::Session;
+use types::{Origin, Metadata, H256};
+use util::capture::Capture;
+use util::informant::{Middleware, RpcStats, ClientNotifier, CpuPool};
+use util::rate_limit::RateLimiter;
+use util::timeouts::TimeoutConfiguration;
+use util::upstream::Upstreams;
 use api;
-use api::apis::ApiSet;
+use health::{Health, HealthMiddleware};
+use api::apis::{Api, ApiSet};
+use tokens::TokenStore;
+use tls::{self, TlsConfiguration, TlsServer, Peers};
+use std::collections::HashSet;
+use std::fs;
 use std::io;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
-use parity_reactor::TokioRemote;
+use parking_lot::RwLock;
+use parity_reactor::{Remote, TokioRemote};
+use helpers::parity_ipc_path;
+use dir::default_data_path;
 
-pub use jsonrpc_http_server::Server as HttpServer;
 pub use jsonrpc_http_server::hyper;
+pub use jsonrpc_ws_server::Server as WsServer;
+pub use jsonrpc_ipc_server::Server as IpcServer;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct HttpConfiguration {
     pub enabled: bool,
     pub interface: String,
@@ -22,6 +41,8 @@ pub struct HttpConfiguration {
     pub apis: ApiSet,
     pub cors: Option<Vec<String>>,
     pub hosts: Option<Vec<String>>,
+    pub auth: bool,
+    pub tls: Option<TlsConfiguration>,
 }
 
 impl Default for HttpConfiguration {
@@ -33,6 +54,50 @@ impl Default for HttpConfiguration {
             apis: ApiSet::UnsafeContext,
             cors: None,
             hosts: Some(Vec::new()),
+            auth: false,
+            tls: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WsConfiguration {
+    pub enabled: bool,
+    pub interface: String,
+    pub port: u16,
+    pub apis: ApiSet,
+    pub origins: Option<Vec<String>>,
+    pub hosts: Option<Vec<String>>,
+    pub auth: bool,
+}
+
+impl Default for WsConfiguration {
+    fn default() -> Self {
+        WsConfiguration {
+            enabled: true,
+            interface: "127.0.0.1".into(),
+            port: 8546,
+            apis: ApiSet::UnsafeContext,
+            origins: Some(Vec::new()),
+            hosts: Some(Vec::new()),
+            auth: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct IpcConfiguration {
+    pub enabled: bool,
+    pub socket_addr: String,
+    pub apis: ApiSet,
+}
+
+impl Default for IpcConfiguration {
+    fn default() -> Self {
+        IpcConfiguration {
+            enabled: true,
+            socket_addr: parity_ipc_path(&default_data_path(), "$BASE/jsonrpc.ipc"),
+            apis: ApiSet::IpcContext,
         }
     }
 }
@@ -41,10 +106,96 @@ pub struct Dependencies {
     pub apis: Arc<api::apis::Dependencies>,
     pub remote: TokioRemote,
     pub stats: Arc<RpcStats>,
+    pub notifier: ClientNotifier,
 	pub pool: Option<CpuPool>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub tokens: Option<Arc<TokenStore>>,
+    pub capture: Option<Arc<Capture>>,
+    pub health: Arc<Health>,
+    pub timeouts: Arc<RwLock<TimeoutConfiguration>>,
+    pub upstreams: Option<Arc<Upstreams>>,
+}
+
+/// Answers health checks, other requests are handled by the RPC server.
+struct HttpMiddleware {
+    health: HealthMiddleware,
+    /// Connections forwarded by the TLS listener, other connections are rejected.
+    peers: Option<Arc<Peers>>,
+}
+
+impl HttpMiddleware {
+    fn forbidden() -> RequestMiddlewareAction {
+        let response = Response {
+            code: hyper::StatusCode::Forbidden,
+            content_type: hyper::header::ContentType::plaintext(),
+            content: "Connections are only accepted through the TLS listener.\n".into(),
+        };
+
+        RequestMiddlewareAction::Respond {
+            should_validate_hosts: false,
+            handler: future::ok(response.into()).boxed(),
+        }
+    }
+}
+
+impl RequestMiddleware for HttpMiddleware {
+    fn on_request(&self, request: &hyper::server::Request) -> RequestMiddlewareAction {
+        if let Some(ref peers) = self.peers {
+            // the plain-text backend listens on loopback, where any local process could reach it directly
+            if !request.remote_addr().map_or(false, |addr| peers.contains(&addr)) {
+                return Self::forbidden();
+            }
+        }
+
+        self.health.on_request(request)
+    }
+}
+
+/// JSON-RPC HTTP server, optionally behind a TLS listener.
+pub struct HttpServer {
+    _tls: Option<TlsServer>,
+    _server: jsonrpc_http_server::Server,
+}
+
+pub struct RpcExtractor {
+    tokens: Option<Arc<TokenStore>>,
+    unauthenticated: Arc<HashSet<Api>>,
+    peers: Option<Arc<Peers>>,
+}
+
+impl RpcExtractor {
+    /// Extractor restricting callers without a valid bearer token to `unauthenticated` APIs.
+    pub fn new(tokens: Option<Arc<TokenStore>>, unauthenticated: HashSet<Api>) -> Self {
+        RpcExtractor {
+            tokens: tokens,
+            unauthenticated: Arc::new(unauthenticated),
+            peers: None,
+        }
+    }
+
+    /// Resolve requests forwarded by the TLS listener to the original peer.
+    pub fn with_peers(mut self, peers: Arc<Peers>) -> Self {
+        self.peers = Some(peers);
+        self
+    }
+
+    /// Returns APIs the caller may use and whether it presented a valid token.
+    fn apis(&self, req: &hyper::server::Request) -> (Option<Arc<HashSet<Api>>>, bool) {
+        let tokens = match self.tokens {
+            Some(ref tokens) => tokens,
+            None => return (None, false),
+        };
+
+        let token_apis = req.headers()
+            .get::<hyper::header::Authorization<hyper::header::Bearer>>()
+            .and_then(|auth| tokens.apis(&auth.token));
+        match token_apis {
+            Some(apis) => (Some(apis), true),
+            None => (Some(self.unauthenticated.clone()), false),
+        }
+    }
 }
 
-pub struct RpcExtractor;
 impl MetaExtractor<Metadata> for RpcExtractor {
     fn read_metadata(&self, req: &hyper::server::Request) -> Metadata {
         let origin = req.headers()
@@ -53,6 +204,130 @@ impl MetaExtractor<Metadata> for RpcExtractor {
             .unwrap_or_else(|| "unknown".into());
         let mut metadata = Metadata::default();
         metadata.origin = Origin::Rpc(origin);
+        metadata.remote_addr = req.remote_addr();
+        if let (Some(peers), Some(addr)) = (self.peers.as_ref(), req.remote_addr()) {
+            if let Some(peer) = peers.get(&addr) {
+                metadata.remote_addr = Some(peer.addr);
+                metadata.peer_subject = peer.subject;
+            }
+        }
+        let (apis, authenticated) = self.apis(req);
+        metadata.apis = apis;
+        metadata.authenticated = authenticated;
+        metadata
+    }
+}
+
+/// Prefix of the WebSocket subprotocol carrying the bearer token, e.g. `bearer.<token>`.
+/// Browsers cannot set the `Authorization` header of a WebSocket handshake.
+pub const WS_TOKEN_PROTOCOL_PREFIX: &'static str = "bearer.";
+
+pub struct WsExtractor {
+    tokens: Option<Arc<TokenStore>>,
+    unauthenticated: Arc<HashSet<Api>>,
+}
+
+impl WsExtractor {
+    /// Extractor restricting sessions without a valid bearer token to `unauthenticated` APIs.
+    /// The token is checked once, when the session is opened.
+    pub fn new(tokens: Option<Arc<TokenStore>>, unauthenticated: HashSet<Api>) -> Self {
+        WsExtractor {
+            tokens: tokens,
+            unauthenticated: Arc::new(unauthenticated),
+        }
+    }
+}
+
+/// Returns the first `bearer.<token>` subprotocol carrying a valid token and APIs the token gives access to.
+fn token_protocol<'a, I>(tokens: &TokenStore, protocols: I) -> Option<(&'a str, Arc<HashSet<Api>>)>
+    where I: IntoIterator<Item = &'a str>
+{
+    protocols.into_iter()
+        .filter(|protocol| protocol.starts_with(WS_TOKEN_PROTOCOL_PREFIX))
+        .filter_map(|protocol| tokens.apis(&protocol[WS_TOKEN_PROTOCOL_PREFIX.len()..]).map(|apis| (protocol, apis)))
+        .next()
+}
+
+impl ws::MetaExtractor<Metadata> for WsExtractor {
+    fn extract(&self, req: &ws::RequestContext) -> Metadata {
+        let mut metadata = Metadata::default();
+        metadata.origin = Origin::Ws(H256::from(req.session_id));
+        metadata.session = Some(Arc::new(Session::new(req.sender())));
+        if let Some(ref tokens) = self.tokens {
+            let token_apis = token_protocol(tokens, req.protocols.iter().map(|protocol| protocol.as_str()))
+                .map(|(_, apis)| apis);
+            metadata.authenticated = token_apis.is_some();
+            metadata.apis = Some(token_apis.unwrap_or_else(|| self.unauthenticated.clone()));
+        }
+        metadata
+    }
+}
+
+/// Selects the token subprotocol in the handshake response.
+/// Browsers abort the handshake if none of the offered subprotocols is echoed back.
+pub struct WsMiddleware {
+    tokens: Option<Arc<TokenStore>>,
+}
+
+impl WsMiddleware {
+    pub fn new(tokens: Option<Arc<TokenStore>>) -> Self {
+        WsMiddleware { tokens: tokens }
+    }
+}
+
+impl ws::RequestMiddleware for WsMiddleware {
+    fn process(&self, req: &ws::ws::Request) -> ws::MiddlewareAction {
+        let tokens = match self.tokens {
+            Some(ref tokens) => tokens,
+            None => return ws::MiddlewareAction::Proceed,
+        };
+        let protocols = req.protocols().unwrap_or_default();
+        let protocol = match token_protocol(tokens, protocols) {
+            Some((protocol, _)) => protocol,
+            None => return ws::MiddlewareAction::Proceed,
+        };
+
+        match ws::ws::Response::from_request(req) {
+            Ok(mut response) => {
+                response.set_protocol(protocol);
+                ws::MiddlewareAction::Respond {
+                    response: response,
+                    validate_origin: true,
+                    validate_hosts: true,
+                }
+            }
+            Err(_) => ws::MiddlewareAction::Proceed,
+        }
+    }
+}
+
+pub struct WsStats {
+    stats: Arc<RpcStats>,
+}
+
+impl WsStats {
+    pub fn new(stats: Arc<RpcStats>) -> Self {
+        WsStats { stats: stats }
+    }
+}
+
+impl ws::SessionStats for WsStats {
+    fn open_session(&self, _id: ws::SessionId) {
+        self.stats.open_session()
+    }
+
+    fn close_session(&self, _id: ws::SessionId) {
+        self.stats.close_session()
+    }
+}
+
+/// IPC callers are not restricted, access to the socket is controlled by filesystem permissions.
+pub struct IpcExtractor;
+impl ipc::MetaExtractor<Metadata> for IpcExtractor {
+    fn extract(&self, req: &ipc::RequestContext) -> Metadata {
+        let mut metadata = Metadata::default();
+        metadata.origin = Origin::Ipc(H256::from(req.session_id));
+        metadata.session = Some(Arc::new(Session::new(req.sender.clone())));
         metadata
     }
 }
@@ -66,21 +341,122 @@ pub fn new_http(conf: HttpConfiguration,
 
     let url = format!("{}:{}", conf.interface, conf.port);
     let addr = url.parse().map_err(|_| format!("Invalid JSONRPC listen host/port given: {}", url))?;
-    Ok(Some(setup_http_rpc_server(deps, &addr, conf.cors, conf.hosts, conf.apis)?))
+    let (apis, tokens, unauthenticated) = authorization(conf.apis, conf.auth, deps);
+    let extractor = RpcExtractor::new(tokens, unauthenticated);
+
+    let tls_conf = match conf.tls {
+        Some(tls_conf) => tls_conf,
+        None => {
+            return Ok(Some(HttpServer {
+                _tls: None,
+                _server: setup_http_rpc_server(deps, &addr, conf.cors, conf.hosts, apis, extractor)?,
+            }))
+        }
+    };
+
+    // plain-text server is only reachable through the TLS listener
+    let acceptor = tls::acceptor(&tls_conf)?;
+    let peers = Arc::new(Peers::default());
+    let localhost = match conf.interface.as_str() {
+        "127.0.0.1" => Some(format!("localhost:{}", conf.port)),
+        _ => None,
+    };
+    let hosts = conf.hosts.map(|mut hosts| {
+        hosts.push(url.clone());
+        hosts.extend(localhost);
+        hosts
+    });
+    let backend = "127.0.0.1:0".parse().expect("Valid socket address; qed");
+    let server = setup_http_rpc_server(deps, &backend, conf.cors, hosts, apis, extractor.with_peers(peers.clone()))?;
+    let tls_server = match tls::start(&addr, server.address().clone(), acceptor, peers, &deps.remote) {
+        Ok(tls_server) => tls_server,
+        Err(ref err) if err.kind() == io::ErrorKind::AddrInUse => {
+            return Err(format!("RPC address {} is already in use, make sure that another instance of a \
+                                Freedom client is not running or change the address using the \
+                                --jsonrpc-port and --jsonrpc-interface options.",
+                               url))
+        }
+        Err(e) => return Err(format!("TLS error: {}", e)),
+    };
+
+    Ok(Some(HttpServer {
+        _tls: Some(tls_server),
+        _server: server,
+    }))
+}
+
+/// Returns APIs served by a transport, token store and APIs available without a token.
+/// With token authorization all APIs are served, but callers without a token are restricted
+/// to the safe subset of configured APIs.
+fn authorization(apis: ApiSet, auth: bool, deps: &Dependencies) -> (ApiSet, Option<Arc<TokenStore>>, HashSet<Api>) {
+    match deps.tokens {
+        Some(ref tokens) if auth => {
+            let modules = &deps.apis.modules;
+            let unauthenticated = apis.list_apis_with(modules)
+                .intersection(&ApiSet::UnsafeContext.list_apis_with(modules))
+                .cloned()
+                .collect();
+            let mut all = ApiSet::SafeContext.list_apis_with(modules);
+            all.extend(apis.list_apis_with(modules));
+            (ApiSet::List(all), Some(tokens.clone()), unauthenticated)
+        }
+        _ => (apis, None, HashSet::new()),
+    }
+}
+
+pub fn new_ws(conf: WsConfiguration,
+              deps: &Dependencies)
+              -> Result<Option<WsServer>, String> {
+    if !conf.enabled {
+        return Ok(None);
+    }
+
+    let url = format!("{}:{}", conf.interface, conf.port);
+    let addr = url.parse().map_err(|_| format!("Invalid WebSockets listen host/port given: {}", url))?;
+    let (apis, tokens, unauthenticated) = authorization(conf.apis, conf.auth, deps);
+    let middleware = WsMiddleware::new(tokens.clone());
+    let extractor = WsExtractor::new(tokens, unauthenticated);
+    Ok(Some(setup_ws_rpc_server(deps, &addr, conf.origins, conf.hosts, apis, extractor, middleware)?))
+}
+
+pub fn new_ipc(conf: IpcConfiguration,
+               deps: &Dependencies)
+               -> Result<Option<IpcServer>, String> {
+    if !conf.enabled {
+        return Ok(None);
+    }
+
+    if let Some(dir) = Path::new(&conf.socket_addr).parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Unable to create IPC directory {}: {}", dir.display(), e))?;
+    }
+
+    let handler = setup_apis(conf.apis, deps);
+    let remote = deps.remote.clone();
+    match start_ipc(&conf.socket_addr, handler, remote, IpcExtractor) {
+        Ok(server) => Ok(Some(server)),
+        Err(io_error) => Err(format!("IPC error: {}", io_error)),
+    }
 }
 
 fn setup_apis(apis: ApiSet, deps: &Dependencies) -> MetaIoHandler<Metadata, Middleware> {
-    api::apis::setup_apis(deps.stats.clone(), deps.apis.clone(), apis, deps.pool.clone())
+    let middleware = Middleware::new(deps.stats.clone(), deps.notifier.clone(), deps.pool.clone())
+        .with_rate_limiter(deps.rate_limiter.clone())
+        .with_capture(deps.capture.clone())
+        .with_timeouts(Remote::new(deps.remote.clone()), deps.timeouts.clone())
+        .with_upstreams(deps.upstreams.clone());
+    api::apis::setup_apis(middleware, deps.apis.clone(), apis)
 }
 
 pub fn setup_http_rpc_server(dependencies: &Dependencies,
                              url: &SocketAddr,
                              cors_domains: Option<Vec<String>>,
                              allowed_hosts: Option<Vec<String>>,
-                             apis: ApiSet)
-                             -> Result<HttpServer, String> {
+                             apis: ApiSet,
+                             extractor: RpcExtractor)
+                             -> Result<jsonrpc_http_server::Server, String> {
     let handler = setup_apis(apis, dependencies);
     let remote = dependencies.remote.clone();
+    let peers = extractor.peers.clone();
     let cors_domains: Option<Vec<_>> = cors_domains.map(|domains| domains.into_iter().map(AccessControlAllowOrigin::from).collect());
     let allowed_hosts: Option<Vec<_>> =
         allowed_hosts.map(|hosts| hosts.into_iter().map(Host::from).collect());
@@ -89,7 +465,11 @@ pub fn setup_http_rpc_server(dependencies: &Dependencies,
                                   allowed_hosts.into(),
                                   handler,
                                   remote,
-                                  RpcExtractor);
+                                  extractor,
+                                  HttpMiddleware {
+                                      health: HealthMiddleware::new(dependencies.health.clone()),
+                                      peers: peers,
+                                  });
     match start_result {
         Ok(server) => Ok(server),
         Err(ref err) if err.kind() == io::ErrorKind::AddrInUse => Err(format!(
@@ -103,22 +483,172 @@ pub fn setup_http_rpc_server(dependencies: &Dependencies,
     }
 }
 
-pub fn start_http<M, S, H, T>(addr: &SocketAddr,
-                              cors_domains: DomainsValidation<AccessControlAllowOrigin>,
-                              allowed_hosts: DomainsValidation<Host>,
-                              handler: H,
-                              remote: TokioRemote,
-                              extractor: T)
-                              -> ::std::io::Result<HttpServer>
+pub fn start_http<M, S, H, T, R>(addr: &SocketAddr,
+                                 cors_domains: DomainsValidation<AccessControlAllowOrigin>,
+                                 allowed_hosts: DomainsValidation<Host>,
+                                 handler: H,
+                                 remote: TokioRemote,
+                                 extractor: T,
+                                 middleware: R)
+                                 -> ::std::io::Result<jsonrpc_http_server::Server>
     where M: jsonrpc_core::Metadata + Default,
           S: jsonrpc_core::Middleware<M>,
           H: Into<jsonrpc_core::MetaIoHandler<M, S>>,
-          T: MetaExtractor<M>
+          T: MetaExtractor<M>,
+          R: RequestMiddleware
 {
     ServerBuilder::new(handler)
         .event_loop_remote(remote)
         .meta_extractor(extractor)
+        .request_middleware(middleware)
         .cors(cors_domains.into())
         .allowed_hosts(allowed_hosts.into())
         .start_http(addr)
 }
+
+pub fn setup_ws_rpc_server(dependencies: &Dependencies,
+                           url: &SocketAddr,
+                           allowed_origins: Option<Vec<String>>,
+                           allowed_hosts: Option<Vec<String>>,
+                           apis: ApiSet,
+                           extractor: WsExtractor,
+                           middleware: WsMiddleware)
+                           -> Result<WsServer, String> {
+    let handler = setup_apis(apis, dependencies);
+    let remote = dependencies.remote.clone();
+    let allowed_origins: Option<Vec<_>> =
+        allowed_origins.map(|origins| origins.into_iter().map(ws::Origin::from).collect());
+    let allowed_hosts: Option<Vec<_>> =
+        allowed_hosts.map(|hosts| hosts.into_iter().map(ws::Host::from).collect());
+    let start_result = start_ws(url,
+                                allowed_origins.into(),
+                                allowed_hosts.into(),
+                                handler,
+                                remote,
+                                extractor,
+                                middleware,
+                                WsStats::new(dependencies.stats.clone()));
+    match start_result {
+        Ok(server) => Ok(server),
+        Err(ws::Error::Io(ref err)) if err.kind() == io::ErrorKind::AddrInUse => Err(format!(
+                                          "WebSockets address {} is already in use, make sure that \
+                                           another instance of a Freedom client is not running or \
+                                           change the address using the --ws-port and --ws-interface \
+                                           options.",
+                                          url
+                                      )),
+        Err(e) => Err(format!("WebSockets error: {:?}", e)),
+    }
+}
+
+pub fn start_ws<M, S, H, T, R, U>(addr: &SocketAddr,
+                                  allowed_origins: ws::DomainsValidation<ws::Origin>,
+                                  allowed_hosts: ws::DomainsValidation<ws::Host>,
+                                  handler: H,
+                                  remote: TokioRemote,
+                                  extractor: T,
+                                  middleware: R,
+                                  stats: U)
+                                  -> Result<WsServer, ws::Error>
+    where M: jsonrpc_core::Metadata,
+          S: jsonrpc_core::Middleware<M>,
+          H: Into<jsonrpc_core::MetaIoHandler<M, S>>,
+          T: ws::MetaExtractor<M>,
+          R: ws::RequestMiddleware,
+          U: ws::SessionStats
+{
+    ws::ServerBuilder::new(handler)
+        .event_loop_remote(remote)
+        .allowed_origins(allowed_origins)
+        .allowed_hosts(allowed_hosts)
+        .session_meta_extractor(extractor)
+        .request_middleware(middleware)
+        .session_stats(stats)
+        .start(addr)
+}
+
+pub fn start_ipc<M, S, H, T>(addr: &str,
+                             handler: H,
+                             remote: TokioRemote,
+                             extractor: T)
+                             -> ::std::io::Result<IpcServer>
+    where M: jsonrpc_core::Metadata,
+          S: jsonrpc_core::Middleware<M>,
+          H: Into<jsonrpc_core::MetaIoHandler<M, S>>,
+          T: ipc::MetaExtractor<M>
+{
+    ipc::ServerBuilder::new(handler)
+        .event_loop_remote(remote)
+        .session_metadata_extractor(extractor)
+        .start(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::env;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::sync::Arc;
+    use jsonrpc_core::MetaIoHandler;
+    use jsonrpc_ws_server as ws;
+    use parity_reactor::EventLoop;
+    use rand::random;
+    use api::apis::{Api, ApiSet};
+    use tokens::TokenStore;
+    use types::Metadata;
+    use util::informant::RpcStats;
+    use super::{start_ws, WsExtractor, WsMiddleware, WsStats};
+
+    fn handshake(addr: &SocketAddr, protocols: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream,
+               "GET / HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+                Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                Sec-WebSocket-Protocol: {}\r\n\r\n",
+               addr,
+               protocols)
+            .unwrap();
+
+        let mut response = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !response.ends_with(b"\r\n\r\n") {
+            let read = stream.read(&mut buf).unwrap();
+            assert!(read > 0, "Connection closed during handshake");
+            response.extend_from_slice(&buf[..read]);
+        }
+        String::from_utf8(response).unwrap()
+    }
+
+    #[test]
+    fn should_echo_token_subprotocol_in_handshake() {
+        // given
+        let path = env::temp_dir().join(format!("toyrpc-ws-tokens-{}.json", random::<u64>()));
+        let tokens = Arc::new(TokenStore::new(path.clone()).unwrap());
+        let token = tokens.generate(&ApiSet::List(vec![Api::Signer].into_iter().collect())).unwrap();
+        let event_loop = EventLoop::spawn();
+        let server = start_ws(&"127.0.0.1:0".parse().unwrap(),
+                              ws::DomainsValidation::Disabled,
+                              ws::DomainsValidation::Disabled,
+                              MetaIoHandler::<Metadata>::default(),
+                              event_loop.raw_remote(),
+                              WsExtractor::new(Some(tokens.clone()), HashSet::new()),
+                              WsMiddleware::new(Some(tokens)),
+                              WsStats::new(Arc::new(RpcStats::default())))
+            .unwrap();
+
+        // when
+        let authorized = handshake(server.addr(), &format!("chat, bearer.{}", token));
+        let unauthorized = handshake(server.addr(), "bearer.invalid");
+
+        // then
+        assert!(authorized.starts_with("HTTP/1.1 101"), "{}", authorized);
+        assert!(authorized.contains(&format!("\r\nSec-WebSocket-Protocol: bearer.{}\r\n", token)),
+                "{}",
+                authorized);
+        assert!(unauthorized.starts_with("HTTP/1.1 101"), "{}", unauthorized);
+        assert!(!unauthorized.contains("Sec-WebSocket-Protocol"), "{}", unauthorized);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -14,39 +14,51 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::cell::RefCell;
 use std::sync::Arc;
-use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+use std::time::Duration;
 use ctrlc;
 use fdlimit::raise_fd_limit;
-use ethcore_logger::RotatingLogger;
 use parking_lot::{Mutex, Condvar};
-use ansi_term::Colour;
 use io::{MayPanic, PanicHandler};
-use ethcore_logger::Config as LogConfig;
-use util::informant::{self, CpuPool, Builder};
-use informant::{Informant};
-use rpc::HttpConfiguration;
-use dir::Directories;
-use user_defaults::UserDefaults;
-use api::apis;
-use rpc;
-use parity_reactor::EventLoop;
-use util::misc::version;
-
-#[derive(Debug, PartialEq)]
+use ethcore_logger::{self, Config as LogConfig, RotatingLogger};
+use toyrpc::{Node, NodeBuilder};
+use toyrpc::util::rate_limit::RateLimitConfiguration;
+use toyrpc::util::timeouts::TimeoutConfiguration;
+use toyrpc::util::upstream::UpstreamConfiguration;
+use toyrpc::util::response_cache::CacheConfiguration;
+use toyrpc::rpc::{HttpConfiguration, WsConfiguration, IpcConfiguration};
+use toyrpc::metrics::MetricsConfiguration;
+use toyrpc::dir::Directories;
+use configuration::{Cmd, Configuration};
+
+/// Set by the SIGHUP handler, cleared once configuration is reloaded.
+static HANGUP: AtomicBool = ATOMIC_BOOL_INIT;
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct RunCmd {
     pub dirs: Directories,
     /// Some if execution should be daemonized. Contains pid_file path.
     pub daemon: Option<String>,
     pub logger_config: LogConfig,
     pub http_conf: HttpConfiguration,
+    pub ws_conf: WsConfiguration,
+    pub ipc_conf: IpcConfiguration,
+    pub metrics_conf: MetricsConfiguration,
+    pub rate_limit_conf: RateLimitConfiguration,
+    pub timeout_conf: TimeoutConfiguration,
+    pub upstream_conf: UpstreamConfiguration,
+    pub cache_conf: CacheConfiguration,
+    pub informant_interval: Duration,
+    pub shutdown_timeout: Duration,
+    pub network_id: u64,
+    /// Whether to record processed requests for replay
+    pub capture: bool,
     pub name: String,
 }
 
-pub fn execute(cmd: RunCmd,
-               can_restart: bool,
-               logger: Arc<RotatingLogger>)
-               -> Result<(bool, Option<String>), String> {
+pub fn execute(cmd: RunCmd, logger: Arc<RotatingLogger>, can_restart: bool) -> Result<(bool, Option<String>), String> {
 
     // set up panic handler
     let panic_handler = PanicHandler::new_in_arc();
@@ -59,40 +71,45 @@ pub fn execute(cmd: RunCmd,
         daemonize(pid_file.into())?;
     }
 
-    info!("Starting {}", Colour::White.bold().paint(version()));
-
-    // spin up event loop
-    let event_loop = EventLoop::spawn();
-
-    // set up dependencies for rpc servers
-    let rpc_stats = Arc::new(informant::RpcStats::default());
-    let deps_for_rpc_apis = Arc::new(apis::Dependencies { logger: logger.clone() });
-
-    let dependencies = rpc::Dependencies {
-        apis: deps_for_rpc_apis.clone(),
-        remote: event_loop.raw_remote(),
-        stats: rpc_stats.clone(),
-        pool: Some(Builder::new().create()),
-    };
-
-    // start rpc servers
-    let http_server = rpc::new_http(cmd.http_conf, &dependencies)?;
-
-    // the informant
-    let informant = Arc::new(Informant::new(Some(rpc_stats.clone()), cmd.logger_config.color));
+    let current = RefCell::new(cmd.clone());
+    let mut node = NodeBuilder::new(cmd.http_conf)
+        .logger(logger)
+        .color(cmd.logger_config.color)
+        .ws(cmd.ws_conf)
+        .ipc(cmd.ipc_conf)
+        .metrics(cmd.metrics_conf)
+        .rate_limit(cmd.rate_limit_conf)
+        .timeouts(cmd.timeout_conf)
+        .upstream(cmd.upstream_conf)
+        .cache(cmd.cache_conf)
+        .dirs(cmd.dirs)
+        .informant_interval(cmd.informant_interval)
+        .shutdown_timeout(cmd.shutdown_timeout)
+        .network_id(cmd.network_id)
+        .capture(cmd.capture)
+        .build();
+    node.start()?;
 
     // Handle exit
-    let restart = wait_for_exit(panic_handler, can_restart);
-
-    // drop this stuff as soon as exit detected.
-    drop((http_server, event_loop));
+    let restart = wait_for_exit(panic_handler, || {
+        match reload_config(&node, &mut current.borrow_mut()) {
+            Ok(ref restart) if restart.is_empty() => info!("Configuration reloaded."),
+            Ok(ref restart) if can_restart => {
+                info!("Configuration reloaded. Restarting to apply changes to {}.", restart.join(", "));
+                return true;
+            }
+            Ok(restart) => {
+                warn!("Configuration reloaded. Changes to {} require a restart, run with --can-restart to restart \
+                       automatically.",
+                      restart.join(", "))
+            }
+            Err(e) => warn!("Unable to reload configuration: {}", e),
+        }
+        false
+    })?;
 
     info!("Finishing work, please wait...");
-
-    // to make sure timer does not spawn requests while shutdown is in progress
-    informant.shutdown();
-    // just Arc is dropping here, to allow other reference release in its default time
-    drop(informant);
+    node.stop();
 
     Ok(restart)
 }
@@ -114,23 +131,136 @@ fn daemonize(_pid_file: String) -> Result<(), String> {
     Err("daemon is no supported on windows".into())
 }
 
-fn wait_for_exit(panic_handler: Arc<PanicHandler>, can_restart: bool) -> (bool, Option<String>) {
-    let exit = Arc::new((Mutex::new((false, None)), Condvar::new()));
+/// Re-reads configuration and applies settings which can change at runtime to the node
+/// started with `current`. Returns changed settings which require a restart.
+fn reload_config(node: &Node, current: &mut RunCmd) -> Result<Vec<&'static str>, String> {
+    let new = match Configuration::parse().map_err(|e| e.message)?.into_command()?.cmd {
+        Cmd::Run(run_cmd) => run_cmd,
+        _ => return Err("Configuration does not describe a node to run.".into()),
+    };
+
+    if new.logger_config.mode != current.logger_config.mode {
+        ethcore_logger::set_log_levels(&ethcore_logger::log_levels(&new.logger_config))?;
+        current.logger_config.mode = new.logger_config.mode.clone();
+    }
+    if new.timeout_conf != current.timeout_conf {
+        node.set_timeouts(new.timeout_conf.clone());
+        current.timeout_conf = new.timeout_conf.clone();
+    }
+    if new.shutdown_timeout != current.shutdown_timeout {
+        node.set_shutdown_timeout(new.shutdown_timeout);
+        current.shutdown_timeout = new.shutdown_timeout;
+    }
+    if new.rate_limit_conf != current.rate_limit_conf && node.set_rate_limits(new.rate_limit_conf.clone()).is_ok() {
+        current.rate_limit_conf = new.rate_limit_conf.clone();
+    }
+    if new.upstream_conf != current.upstream_conf && node.set_upstream(new.upstream_conf.clone()).is_ok() {
+        current.upstream_conf = new.upstream_conf.clone();
+    }
+    if new.cache_conf != current.cache_conf && node.set_cache(new.cache_conf.clone()).is_ok() {
+        current.cache_conf = new.cache_conf.clone();
+    }
+
+    Ok(restart_required(current, &new))
+}
+
+/// Returns names of settings which differ between the running node and new configuration.
+fn restart_required(current: &RunCmd, new: &RunCmd) -> Vec<&'static str> {
+    let changes = [("operating.base_path", current.dirs != new.dirs),
+                   ("operating.daemon", current.daemon != new.daemon),
+                   ("operating.network_id", current.network_id != new.network_id),
+                   ("operating.capture", current.capture != new.capture),
+                   ("operating.identity", current.name != new.name),
+                   ("rpc", current.http_conf != new.http_conf),
+                   ("websockets", current.ws_conf != new.ws_conf),
+                   ("ipc", current.ipc_conf != new.ipc_conf),
+                   ("metrics", current.metrics_conf != new.metrics_conf),
+                   ("rate_limit", current.rate_limit_conf != new.rate_limit_conf),
+                   ("upstream", current.upstream_conf != new.upstream_conf),
+                   ("cache", current.cache_conf != new.cache_conf),
+                   ("logging", current.logger_config != new.logger_config),
+                   ("logging.informant_interval", current.informant_interval != new.informant_interval)];
+    changes.iter().filter(|&&(_, changed)| changed).map(|&(key, _)| key).collect()
+}
+
+/// Replaces the SIGHUP handler, so that SIGHUP sets `HANGUP` instead of terminating the node.
+#[cfg(unix)]
+fn listen_for_hangup() {
+    extern "C" fn on_hangup(_signal: ::libc::c_int) {
+        HANGUP.store(true, Ordering::SeqCst);
+    }
+
+    unsafe {
+        ::libc::signal(::libc::SIGHUP, on_hangup as ::libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn listen_for_hangup() {}
 
-    // Handle possible exits
+/// Blocks until the node should stop. Returns whether it should be restarted, which happens
+/// when `on_hangup` asks for it after reloading configuration.
+fn wait_for_exit<F: Fn() -> bool>(panic_handler: Arc<PanicHandler>,
+                                  on_hangup: F)
+                                  -> Result<(bool, Option<String>), String> {
+    let exit = Arc::new((Mutex::new(None), Condvar::new()));
+
+    // Handle possible exits (SIGINT, SIGTERM)
     let e = exit.clone();
-    ctrlc::set_handler(move || {
+    let handler = ctrlc::set_handler(move || {
+        *e.0.lock() = Some((false, None));
         e.1.notify_all();
     });
+    handler.map_err(|e| format!("Unable to handle termination signals: {:?}", e))?;
 
     // Handle panics
     let e = exit.clone();
     panic_handler.on_panic(move |_reason| {
+        *e.0.lock() = Some((false, None));
         e.1.notify_all();
     });
 
+    // Handle configuration reloads (SIGHUP). The `termination` feature of `ctrlc` treats SIGHUP
+    // as a termination signal too, so this has to be registered after `ctrlc::set_handler` to replace it.
+    listen_for_hangup();
+
     // Wait for signal
     let mut l = exit.0.lock();
-    let _ = exit.1.wait(&mut l);
-    l.clone()
+    loop {
+        if let Some(ref result) = *l {
+            return Ok(result.clone());
+        }
+
+        let _ = exit.1.wait_for(&mut l, Duration::from_millis(250));
+        if HANGUP.swap(false, Ordering::SeqCst) && on_hangup() {
+            return Ok((true, None));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use configuration::{Cmd, Configuration};
+    use super::{restart_required, RunCmd};
+
+    fn run_cmd(args: Vec<&str>) -> RunCmd {
+        match Configuration::parse_from(args).unwrap().into_command().unwrap().cmd {
+            Cmd::Run(run_cmd) => run_cmd,
+            _ => panic!("Expected run command"),
+        }
+    }
+
+    #[test]
+    fn should_list_changes_requiring_restart() {
+        // given
+        let current = run_cmd(vec!["toyrpc"]);
+        let new = run_cmd(vec!["toyrpc", "--jsonrpc-port", "9090", "--no-ipc", "--request-timeout", "5seconds"]);
+
+        // when
+        let restart = restart_required(&current, &new);
+
+        // then
+        assert_eq!(restart, vec!["rpc", "ipc"]);
+        assert!(restart_required(&current, &current.clone()).is_empty());
+    }
 }
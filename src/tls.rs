@@ -0,0 +1,375 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! TLS termination for the JSON-RPC HTTP server.
+//!
+//! Connections are decrypted on the event loop and forwarded to the plain-text
+//! server listening on loopback. Each forwarded connection is recorded in `Peers`
+//! so the metadata extractor can recover the original peer and its certificate,
+//! and so the plain-text server can reject connections that bypass the listener.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::net::{self as std_net, SocketAddr};
+use std::sync::Arc;
+use futures::{future, Future, IntoFuture, Stream};
+use futures::sync::oneshot;
+use openssl::nid;
+use openssl::pkey::PKey;
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslMethod, SSL_VERIFY_PEER, SSL_VERIFY_FAIL_IF_NO_PEER_CERT};
+use openssl::x509::{X509, X509NameRef};
+use parking_lot::RwLock;
+use parity_reactor::TokioRemote;
+use tokio_core::net::{TcpListener, TcpStream};
+use tokio_core::reactor::Handle;
+use tokio_io::AsyncRead;
+use tokio_io::io::{copy, shutdown};
+use tokio_openssl::SslAcceptorExt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TlsConfiguration {
+    /// Path to PEM encoded server certificate.
+    pub cert: String,
+    /// Path to PEM encoded server private key.
+    pub key: String,
+    /// Path to PEM encoded CA certificates. When given clients must present a certificate signed by one of them.
+    pub client_ca: Option<String>,
+}
+
+/// Client of the TLS listener.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peer {
+    /// Remote address of the client
+    pub addr: SocketAddr,
+    /// Subject of the client certificate (if presented)
+    pub subject: Option<String>,
+}
+
+/// Forwarded connections indexed by their local address.
+#[derive(Default)]
+pub struct Peers {
+    peers: RwLock<HashMap<SocketAddr, Peer>>,
+}
+
+impl Peers {
+    /// Returns TLS peer behind forwarded connection coming from given address.
+    pub fn get(&self, addr: &SocketAddr) -> Option<Peer> {
+        self.peers.read().get(addr).cloned()
+    }
+
+    /// Returns true if connection from given address was forwarded by the TLS listener.
+    pub fn contains(&self, addr: &SocketAddr) -> bool {
+        self.peers.read().contains_key(addr)
+    }
+
+    fn insert(&self, addr: SocketAddr, peer: Peer) {
+        self.peers.write().insert(addr, peer);
+    }
+
+    fn remove(&self, addr: &SocketAddr) {
+        self.peers.write().remove(addr);
+    }
+}
+
+/// TLS listener. Stops accepting connections when dropped.
+pub struct TlsServer {
+    addr: SocketAddr,
+    close: Option<oneshot::Sender<()>>,
+}
+
+impl TlsServer {
+    /// Returns address the server is listening on.
+    pub fn addr(&self) -> &SocketAddr {
+        &self.addr
+    }
+}
+
+impl Drop for TlsServer {
+    fn drop(&mut self) {
+        if let Some(close) = self.close.take() {
+            let _ = close.send(());
+        }
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    let mut contents = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut contents))
+        .map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    Ok(contents)
+}
+
+/// Creates TLS acceptor from given configuration.
+pub fn acceptor(conf: &TlsConfiguration) -> Result<SslAcceptor, String> {
+    let cert = X509::from_pem(&read_file(&conf.cert)?)
+        .map_err(|e| format!("Invalid TLS certificate {}: {}", conf.cert, e))?;
+    let key = PKey::private_key_from_pem(&read_file(&conf.key)?)
+        .map_err(|e| format!("Invalid TLS private key {}: {}", conf.key, e))?;
+
+    let mut builder = SslAcceptorBuilder::mozilla_intermediate(SslMethod::tls(), &key, &cert, Vec::<X509>::new())
+        .map_err(|e| format!("Unable to configure TLS: {}", e))?;
+    if let Some(ref client_ca) = conf.client_ca {
+        let context = builder.builder_mut();
+        context.set_ca_file(client_ca)
+            .map_err(|e| format!("Invalid TLS client CA {}: {}", client_ca, e))?;
+        context.set_verify(SSL_VERIFY_PEER | SSL_VERIFY_FAIL_IF_NO_PEER_CERT);
+    }
+
+    Ok(builder.build())
+}
+
+/// Formats certificate subject, e.g. `CN=node, O=Parity`.
+pub fn subject(name: &X509NameRef) -> String {
+    [(nid::COMMONNAME, "CN"),
+     (nid::ORGANIZATIONALUNITNAME, "OU"),
+     (nid::ORGANIZATIONNAME, "O"),
+     (nid::COUNTRYNAME, "C")]
+        .iter()
+        .flat_map(|&(nid, label)| {
+            name.entries_by_nid(nid)
+                .filter_map(move |entry| entry.data().as_utf8().ok().map(|data| format!("{}={}", label, &*data)))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Starts TLS listener on `addr` forwarding decrypted traffic to `backend`.
+pub fn start(addr: &SocketAddr,
+             backend: SocketAddr,
+             acceptor: SslAcceptor,
+             peers: Arc<Peers>,
+             remote: &TokioRemote)
+             -> io::Result<TlsServer> {
+    let listener = std_net::TcpListener::bind(addr)?;
+    let addr = listener.local_addr()?;
+    let acceptor = Arc::new(acceptor);
+    let (close, closed) = oneshot::channel();
+
+    remote.spawn(move |handle| {
+        let handle = handle.clone();
+        TcpListener::from_listener(listener, &addr, &handle)
+            .into_future()
+            .and_then(move |listener| {
+                listener.incoming().for_each(move |(stream, peer_addr)| {
+                    handle.spawn(forward(stream, peer_addr, backend, acceptor.clone(), peers.clone(), &handle));
+                    Ok(())
+                })
+            })
+            .map_err(|e| warn!(target: "rpc", "TLS listener error: {}", e))
+            .select(closed.map_err(|_| ()))
+            .then(|_| Ok(()))
+    });
+
+    Ok(TlsServer {
+        addr: addr,
+        close: Some(close),
+    })
+}
+
+fn forward(stream: TcpStream,
+           peer_addr: SocketAddr,
+           backend: SocketAddr,
+           acceptor: Arc<SslAcceptor>,
+           peers: Arc<Peers>,
+           handle: &Handle)
+           -> Box<Future<Item = (), Error = ()>> {
+    let handle = handle.clone();
+    let future = acceptor.accept_async(stream)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("TLS handshake failed: {:?}", e)))
+        .and_then(move |tls| {
+            let subject = tls.get_ref().ssl().peer_certificate().map(|cert| subject(cert.subject_name()));
+            TcpStream::connect(&backend, &handle).and_then(move |plain| {
+                future::result(plain.local_addr()).and_then(move |local_addr| {
+                    peers.insert(local_addr,
+                                 Peer {
+                                     addr: peer_addr,
+                                     subject: subject,
+                                 });
+
+                    let (tls_reader, tls_writer) = tls.split();
+                    let (plain_reader, plain_writer) = plain.split();
+                    let upstream = copy(tls_reader, plain_writer).and_then(|(_, _, writer)| shutdown(writer));
+                    let downstream = copy(plain_reader, tls_writer).and_then(|(_, _, writer)| shutdown(writer));
+                    upstream.join(downstream).then(move |result| {
+                        peers.remove(&local_addr);
+                        result.map(|_| ())
+                    })
+                })
+            })
+        })
+        .map_err(move |e| debug!(target: "rpc", "TLS connection from {} closed: {}", peer_addr, e));
+
+    Box::new(future)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+    use std::sync::mpsc;
+    use std::thread;
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::hash::MessageDigest;
+    use openssl::nid;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::ssl::{SslConnectorBuilder, SslMethod};
+    use openssl::x509::{X509, X509Builder, X509NameBuilder};
+    use parity_reactor::EventLoop;
+    use rand::random;
+    use super::{acceptor, start, Peers, TlsConfiguration};
+
+    fn self_signed(common_name: &str) -> (PKey, X509) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(nid::COMMONNAME, common_name).unwrap();
+        let name = name.build();
+
+        let mut cert = X509Builder::new().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_serial_number(&BigNum::from_u32(random()).unwrap().to_asn1_integer().unwrap()).unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        (key, cert.build())
+    }
+
+    static NEXT_DIR: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    /// Directory unique to the test, removed with its contents on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let name = format!("toyrpc-tls-{}-{}", process::id(), NEXT_DIR.fetch_add(1, Ordering::SeqCst));
+            let path = env::temp_dir().join(name);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn write_pem(&self, name: &str, pem: &[u8]) -> String {
+            let path = self.0.join(format!("{}.pem", name));
+            File::create(&path).unwrap().write_all(pem).unwrap();
+            path.to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn should_forward_traffic_and_expose_client_subject() {
+        // given
+        let (server_key, server_cert) = self_signed("server");
+        let (client_key, client_cert) = self_signed("client");
+        let dir = TempDir::new();
+        let conf = TlsConfiguration {
+            cert: dir.write_pem("server-cert", &server_cert.to_pem().unwrap()),
+            key: dir.write_pem("server-key", &server_key.private_key_to_pem().unwrap()),
+            client_ca: Some(dir.write_pem("client-ca", &client_cert.to_pem().unwrap())),
+        };
+
+        let backend = TcpListener::bind("127.0.0.1:0").unwrap();
+        let backend_addr = backend.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, addr) = backend.accept().unwrap();
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).unwrap();
+            tx.send(addr).unwrap();
+            stream.write_all(b"pong").unwrap();
+        });
+
+        let event_loop = EventLoop::spawn();
+        let peers = Arc::new(Peers::default());
+        let server = start(&"127.0.0.1:0".parse().unwrap(),
+                           backend_addr,
+                           acceptor(&conf).unwrap(),
+                           peers.clone(),
+                           &event_loop.raw_remote())
+            .unwrap();
+
+        let mut connector = SslConnectorBuilder::new(SslMethod::tls()).unwrap();
+        connector.builder_mut().set_certificate(&client_cert).unwrap();
+        connector.builder_mut().set_private_key(&client_key).unwrap();
+        let connector = connector.build();
+
+        // when
+        let stream = TcpStream::connect(server.addr()).unwrap();
+        let mut stream = connector.danger_connect_without_providing_domain_for_certificate_verification_and_server_name_indication(stream)
+            .unwrap();
+        stream.write_all(b"ping").unwrap();
+        let mut response = [0u8; 4];
+        stream.read_exact(&mut response).unwrap();
+
+        // then
+        assert_eq!(&response, b"pong");
+        let forwarded = rx.recv().unwrap();
+        let peer = peers.get(&forwarded).unwrap();
+        assert_eq!(peer.subject, Some("CN=client".into()));
+        assert_eq!(peer.addr.ip(), stream.get_ref().local_addr().unwrap().ip());
+    }
+
+    #[test]
+    fn should_reject_client_without_certificate() {
+        // given
+        let (server_key, server_cert) = self_signed("server");
+        let (_, client_cert) = self_signed("client");
+        let dir = TempDir::new();
+        let conf = TlsConfiguration {
+            cert: dir.write_pem("server-cert", &server_cert.to_pem().unwrap()),
+            key: dir.write_pem("server-key", &server_key.private_key_to_pem().unwrap()),
+            client_ca: Some(dir.write_pem("client-ca", &client_cert.to_pem().unwrap())),
+        };
+
+        let event_loop = EventLoop::spawn();
+        let server = start(&"127.0.0.1:0".parse().unwrap(),
+                           "127.0.0.1:1".parse().unwrap(),
+                           acceptor(&conf).unwrap(),
+                           Arc::new(Peers::default()),
+                           &event_loop.raw_remote())
+            .unwrap();
+        let connector = SslConnectorBuilder::new(SslMethod::tls()).unwrap().build();
+
+        // when
+        let stream = TcpStream::connect(server.addr()).unwrap();
+        let rejected = match connector.danger_connect_without_providing_domain_for_certificate_verification_and_server_name_indication(stream) {
+            Ok(mut stream) => {
+                let _ = stream.write_all(b"ping");
+                let mut response = [0u8; 4];
+                stream.read_exact(&mut response).is_err()
+            }
+            Err(_) => true,
+        };
+
+        // then
+        assert!(rejected);
+    }
+}
@@ -0,0 +1,261 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Bearer tokens authorizing access to unsafe APIs.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use parking_lot::RwLock;
+use rand::{Rng, OsRng};
+use rustc_serialize::hex::ToHex;
+use serde_json;
+use api::apis::{Api, ApiSet};
+use dir::Directories;
+
+#[derive(Debug, PartialEq)]
+pub enum TokensAction {
+    Generate(ApiSet),
+    List,
+    Revoke(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TokensCmd {
+    pub dirs: Directories,
+    pub action: TokensAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TokenEntry {
+    apis: Vec<String>,
+    created: u64,
+}
+
+struct Tokens {
+    modified: Option<SystemTime>,
+    entries: BTreeMap<String, TokenEntry>,
+    apis: BTreeMap<String, Arc<HashSet<Api>>>,
+}
+
+/// Tokens persisted in a JSON file. File changes are picked up without restart.
+pub struct TokenStore {
+    path: PathBuf,
+    tokens: RwLock<Tokens>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn read_entries(path: &Path) -> Result<BTreeMap<String, TokenEntry>, String> {
+    let mut contents = String::new();
+    match File::open(path) {
+        Ok(mut file) => {
+            file.read_to_string(&mut contents)
+                .map_err(|e| format!("Unable to read tokens file {}: {}", path.display(), e))?;
+        }
+        Err(_) => return Ok(BTreeMap::new()),
+    }
+
+    serde_json::from_str(&contents).map_err(|e| format!("Invalid tokens file {}: {}", path.display(), e))
+}
+
+impl TokenStore {
+    /// Loads tokens from given file. Missing file means no tokens.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Result<Self, String> {
+        let store = TokenStore {
+            path: path.into(),
+            tokens: RwLock::new(Tokens {
+                modified: None,
+                entries: BTreeMap::new(),
+                apis: BTreeMap::new(),
+            }),
+        };
+        store.reload()?;
+        Ok(store)
+    }
+
+    fn reload(&self) -> Result<(), String> {
+        let modified = modified(&self.path);
+        let entries = read_entries(&self.path)?;
+        let apis = entries.iter()
+            .map(|(token, entry)| {
+                let apis = entry.apis.iter().filter_map(|api| api.parse().ok()).collect();
+                (token.clone(), Arc::new(apis))
+            })
+            .collect();
+
+        *self.tokens.write() = Tokens {
+            modified: modified,
+            entries: entries,
+            apis: apis,
+        };
+        Ok(())
+    }
+
+    fn reload_if_modified(&self) {
+        if modified(&self.path) != self.tokens.read().modified {
+            if let Err(e) = self.reload() {
+                warn!(target: "rpc", "{}", e);
+            }
+        }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let tokens = self.tokens.read();
+        let contents = serde_json::to_string_pretty(&tokens.entries).expect("Tokens serialization is infallible; qed");
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Unable to create directory {}: {}", dir.display(), e))?;
+        }
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        restrict_permissions(&mut options);
+        options.open(&self.path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(|e| format!("Unable to write tokens file {}: {}", self.path.display(), e))
+    }
+
+    /// Returns APIs given token is authorized to use.
+    /// Every stored token is compared in constant time, so timing does not reveal matching prefixes.
+    pub fn apis(&self, token: &str) -> Option<Arc<HashSet<Api>>> {
+        self.reload_if_modified();
+        let tokens = self.tokens.read();
+        let mut found = None;
+        for (candidate, apis) in &tokens.apis {
+            if constant_time_eq(candidate.as_bytes(), token.as_bytes()) {
+                found = Some(apis.clone());
+            }
+        }
+        found
+    }
+
+    /// Generates and persists new token for given APIs.
+    pub fn generate(&self, apis: &ApiSet) -> Result<String, String> {
+        let mut rng = OsRng::new().map_err(|e| format!("Unable to initialize random generator: {}", e))?;
+        let mut bytes = [0u8; 32];
+        rng.fill_bytes(&mut bytes);
+        let token = bytes.to_hex();
+
+        let mut apis = apis.list_apis().into_iter().map(|api| api.to_string()).collect::<Vec<_>>();
+        apis.sort();
+        let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+        self.reload()?;
+        self.tokens.write().entries.insert(token.clone(),
+                                           TokenEntry {
+                                               apis: apis,
+                                               created: created,
+                                           });
+        self.save()?;
+        self.reload()?;
+        Ok(token)
+    }
+
+    /// Revokes given token. Returns false if token was not found.
+    pub fn revoke(&self, token: &str) -> Result<bool, String> {
+        self.reload()?;
+        let removed = self.tokens.write().entries.remove(token).is_some();
+        if removed {
+            self.save()?;
+            self.reload()?;
+        }
+        Ok(removed)
+    }
+
+    /// Returns all tokens with APIs they give access to.
+    pub fn list(&self) -> Vec<(String, Vec<String>)> {
+        self.reload_if_modified();
+        self.tokens.read().entries.iter().map(|(token, entry)| (token.clone(), entry.apis.clone())).collect()
+    }
+}
+
+/// Compares byte strings without stopping at the first difference.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(unix)]
+fn restrict_permissions(options: &mut OpenOptions) {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_options: &mut OpenOptions) {}
+
+pub fn execute(cmd: TokensCmd) -> Result<String, String> {
+    let store = TokenStore::new(cmd.dirs.tokens_path())?;
+    match cmd.action {
+        TokensAction::Generate(apis) => store.generate(&apis),
+        TokensAction::List => {
+            Ok(store.list()
+                .into_iter()
+                .map(|(token, apis)| format!("{} {}", token, apis.join(",")))
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+        TokensAction::Revoke(token) => {
+            match store.revoke(&token)? {
+                true => Ok(format!("Token {} revoked.", token)),
+                false => Err(format!("Token {} not found.", token)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use api::apis::{Api, ApiSet};
+    use rand::random;
+    use super::TokenStore;
+
+    #[test]
+    fn should_generate_and_revoke_tokens() {
+        // given
+        let path = env::temp_dir().join(format!("toyrpc-tokens-{}.json", random::<u64>()));
+        let store = TokenStore::new(path.clone()).unwrap();
+        let apis = ApiSet::List(vec![Api::ParitySet, Api::Signer].into_iter().collect());
+
+        // when
+        let token = store.generate(&apis).unwrap();
+
+        // then
+        assert_eq!(store.apis(&token).unwrap().len(), 2);
+        assert!(store.apis(&token).unwrap().contains(&Api::Signer));
+        assert!(store.apis("invalid").is_none());
+        let last = if token.ends_with('0') { "1" } else { "0" };
+        assert!(store.apis(&format!("{}{}", &token[..token.len() - 1], last)).is_none());
+        assert_eq!(TokenStore::new(path.clone()).unwrap().list(), vec![(token.clone(), vec!["parity_set".to_owned(), "signer".to_owned()])]);
+
+        // when
+        let revoked = store.revoke(&token).unwrap();
+
+        // then
+        assert!(revoked);
+        assert!(store.apis(&token).is_none());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -17,4 +17,14 @@
 //! Ethereum rpc interfaces.
 
 pub mod rpc;
+pub mod web3;
+pub mod net;
+pub mod pubsub;
+pub mod parity;
+pub mod parity_set;
 pub use self::rpc::Rpc;
+pub use self::web3::Web3;
+pub use self::net::Net;
+pub use self::pubsub::PubSub;
+pub use self::parity::Parity;
+pub use self::parity_set::ParitySet;
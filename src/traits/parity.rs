@@ -0,0 +1,33 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Parity-specific rpc interface.
+
+use jsonrpc_core::Error;
+use jsonrpc_macros::Trailing;
+use types::LogsFilter;
+
+build_rpc_trait! {
+	/// Parity-specific rpc interface.
+	pub trait Parity {
+		/// Returns recent log lines, newest first.
+		#[rpc(name = "parity_devLogs")]
+		fn dev_logs(&self, Trailing<LogsFilter>) -> Result<Vec<String>, Error>;
+
+		/// Returns current log levels.
+		#[rpc(name = "parity_devLogsLevels")]
+		fn dev_logs_levels(&self) -> Result<String, Error>;
+	}
+}
@@ -0,0 +1,31 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Parity-specific rpc interface for operations altering the settings.
+
+use jsonrpc_core::Error;
+
+build_rpc_trait! {
+	/// Parity-specific rpc interface for operations altering the settings.
+	pub trait ParitySet {
+		/// Changes log levels of the running node. Uses the same format as RUST_LOG.
+		#[rpc(name = "parity_setLogLevel")]
+		fn set_log_level(&self, String) -> Result<bool, Error>;
+
+		/// Removes all cached responses. Returns false if response caching is disabled.
+		#[rpc(name = "parity_flushCache")]
+		fn flush_cache(&self) -> Result<bool, Error>;
+	}
+}
@@ -0,0 +1,37 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! PubSub interface.
+
+use jsonrpc_core::{Error, Value, BoxFuture};
+use jsonrpc_macros::pubsub;
+use jsonrpc_pubsub::SubscriptionId;
+
+build_rpc_trait! {
+	/// PubSub Interface.
+	pub trait PubSub {
+		type Metadata;
+
+		#[pubsub(name = "pubsub_subscription")] {
+			/// Subscribe to server-push notifications for given topic.
+			#[rpc(name = "pubsub_subscribe")]
+			fn subscribe(&self, Self::Metadata, pubsub::Subscriber<Value>, String);
+
+			/// Unsubscribe from existing subscription.
+			#[rpc(name = "pubsub_unsubscribe")]
+			fn unsubscribe(&self, SubscriptionId) -> BoxFuture<bool, Error>;
+		}
+	}
+}
@@ -19,6 +19,7 @@
 use std::collections::BTreeMap;
 
 use jsonrpc_core::Error;
+use types::DiscoverDocument;
 
 build_rpc_trait! {
 	/// RPC Interface.
@@ -32,5 +33,9 @@ build_rpc_trait! {
         /// @ignore
 		#[rpc(name = "rpc_modules")]
 		fn rpc_modules(&self) -> Result<BTreeMap<String, String>, Error>;
+
+		/// Returns OpenRPC-style description of all available methods
+		#[rpc(name = "rpc_discover")]
+		fn discover(&self) -> Result<DiscoverDocument, Error>;
 	}
 }
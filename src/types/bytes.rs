@@ -0,0 +1,126 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Serializable wrapper around vector of bytes
+
+use std::fmt;
+use rustc_serialize::hex::{ToHex, FromHex};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{Error, Visitor};
+
+/// Wrapper structure around vector of bytes.
+#[derive(Debug, PartialEq, Eq, Default, Hash, Clone)]
+pub struct Bytes(pub Vec<u8>);
+
+impl Bytes {
+    /// Simple constructor.
+    pub fn new(bytes: Vec<u8>) -> Bytes {
+        Bytes(bytes)
+    }
+
+    /// Convert back to vector
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Bytes {
+        Bytes(bytes)
+    }
+}
+
+impl Into<Vec<u8>> for Bytes {
+    fn into(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let mut serialized = "0x".to_owned();
+        serialized.push_str(self.0.to_hex().as_ref());
+        serializer.serialize_str(serialized.as_ref())
+    }
+}
+
+impl<'a> Deserialize<'a> for Bytes {
+    fn deserialize<D>(deserializer: D) -> Result<Bytes, D::Error>
+        where D: Deserializer<'a>
+    {
+        deserializer.deserialize_any(BytesVisitor)
+    }
+}
+
+struct BytesVisitor;
+
+impl<'a> Visitor<'a> for BytesVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a 0x-prefixed, hex-encoded vector of bytes")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where E: Error
+    {
+        if value.len() >= 2 && &value[0..2] == "0x" && value.len() & 1 == 0 {
+            Ok(Bytes::new(FromHex::from_hex(&value[2..]).map_err(|e| E::custom(format!("Invalid hex: {}", e)))?))
+        } else {
+            Err(E::custom("Invalid bytes format. Expected a 0x-prefixed hex string with even length"))
+        }
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+        where E: Error
+    {
+        self.visit_str(value.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use super::Bytes;
+
+    #[test]
+    fn should_serialize_bytes() {
+        // given
+        let bytes = Bytes(vec![0x12, 0xab]);
+
+        // when
+        let serialized = serde_json::to_string(&bytes).unwrap();
+
+        // then
+        assert_eq!(serialized, r#""0x12ab""#);
+    }
+
+    #[test]
+    fn should_deserialize_bytes() {
+        // given
+        let valid = r#"["0x", "0x12ab"]"#;
+        let invalid = r#""0x123""#;
+
+        // when
+        let bytes: Vec<Bytes> = serde_json::from_str(valid).unwrap();
+
+        // then
+        assert_eq!(bytes, vec![Bytes(vec![]), Bytes(vec![0x12, 0xab])]);
+        assert!(serde_json::from_str::<Bytes>(invalid).is_err());
+        assert!(serde_json::from_str::<Bytes>(r#""12ab""#).is_err());
+    }
+}
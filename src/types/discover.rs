@@ -0,0 +1,112 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde_json::{Map, Value};
+
+/// OpenRPC-style description of methods served by the node.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct DiscoverDocument {
+    /// OpenRPC specification version the document follows
+    pub openrpc: String,
+    /// Node information
+    pub info: DiscoverInfo,
+    /// Available methods sorted by name
+    pub methods: Vec<MethodDescription>,
+}
+
+/// Node information.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct DiscoverInfo {
+    /// Name of the node software
+    pub title: String,
+    /// Version of the node software
+    pub version: String,
+}
+
+/// Description of a single method.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MethodDescription {
+    /// Method name
+    pub name: String,
+    /// Parameters in positional order
+    pub params: Vec<ContentDescriptor>,
+    /// Method result
+    pub result: ContentDescriptor,
+    /// Module the method belongs to
+    #[serde(rename="x-module")]
+    pub module: String,
+    /// Safety class of the module (`safe`, `semi-safe` or `unsafe`)
+    #[serde(rename="x-safety")]
+    pub safety: String,
+}
+
+/// Describes a parameter or a result.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContentDescriptor {
+    /// Name of the parameter or result
+    pub name: String,
+    /// Whether the parameter has to be given
+    pub required: bool,
+    /// JSON Schema of the value
+    pub schema: Value,
+}
+
+impl ContentDescriptor {
+    /// Creates descriptor of a required value of given JSON Schema type
+    /// (`string`, `integer`, `boolean`, `object`, `array`, ...).
+    pub fn new<N: Into<String>>(name: N, kind: &str) -> Self {
+        let mut schema = Map::new();
+        schema.insert("type".into(), Value::String(kind.into()));
+        ContentDescriptor {
+            name: name.into(),
+            required: true,
+            schema: Value::Object(schema),
+        }
+    }
+
+    /// Creates descriptor of a value of unknown type.
+    pub fn any<N: Into<String>>(name: N) -> Self {
+        ContentDescriptor {
+            name: name.into(),
+            required: true,
+            schema: Value::Object(Default::default()),
+        }
+    }
+
+    /// Marks the parameter as optional.
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use super::ContentDescriptor;
+
+    #[test]
+    fn should_serialize_content_descriptor() {
+        // given
+        let descriptor = ContentDescriptor::new("filter", "object").optional();
+
+        // when
+        let serialized = serde_json::to_string(&descriptor).unwrap();
+
+        // then
+        assert_eq!(serialized, r#"{"name":"filter","required":false,"schema":{"type":"object"}}"#);
+    }
+}
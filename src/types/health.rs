@@ -0,0 +1,55 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use types::RpcStatsInfo;
+
+/// Health of the node reported by `/health` and `/ready`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthStatus {
+    /// Whether all configured servers are started and the node is not shutting down
+    pub ready: bool,
+    /// Seconds since the node was started
+    pub uptime: u64,
+    /// Client version
+    pub version: String,
+    /// RPC statistics
+    pub stats: RpcStatsInfo,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use types::RpcStatsInfo;
+    use super::HealthStatus;
+
+    #[test]
+    fn should_serialize_health_status() {
+        // given
+        let status = HealthStatus {
+            ready: true,
+            uptime: 42,
+            version: "toyrpc".into(),
+            stats: RpcStatsInfo::default(),
+        };
+
+        // when
+        let res = serde_json::to_string(&status).unwrap();
+
+        // then
+        assert_eq!(res,
+                   r#"{"ready":true,"uptime":42,"version":"toyrpc","stats":{"requestsRate":0,"sessions":0,"approximatedRoundtrip":0}}"#);
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+use ethcore_logger::LogEntry;
+use rlog::LogLevel;
+
+/// Filter for recent log lines.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogsFilter {
+    /// Return only entries at least as severe as given level
+    pub level: Option<String>,
+    /// Return only entries of given target (and its submodules)
+    pub target: Option<String>,
+    /// Number of matching entries to skip (newest first)
+    pub offset: Option<usize>,
+    /// Maximal number of entries to return
+    pub limit: Option<usize>,
+}
+
+impl LogsFilter {
+    /// Parses minimal level of the filter.
+    pub fn level(&self) -> Result<Option<LogLevel>, String> {
+        match self.level {
+            Some(ref level) => level.parse().map(Some).map_err(|_| format!("Invalid log level: {}", level)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns true if entry passes level and target criteria.
+    pub fn matches(&self, level: Option<LogLevel>, entry: &LogEntry) -> bool {
+        let level_matches = level.map_or(true, |level| entry.level <= level);
+        let target_matches = self.target.as_ref().map_or(true, |target| {
+            entry.target == *target || entry.target.starts_with(&format!("{}::", target))
+        });
+        level_matches && target_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use ethcore_logger::LogEntry;
+    use rlog::LogLevel;
+    use super::LogsFilter;
+
+    fn entry(level: LogLevel, target: &str) -> LogEntry {
+        LogEntry {
+            level: level,
+            target: target.into(),
+            line: "line".into(),
+        }
+    }
+
+    #[test]
+    fn should_deserialize_logs_filter() {
+        // given
+        let s = r#"{"level":"warn","target":"rpc","limit":10}"#;
+
+        // when
+        let filter: LogsFilter = serde_json::from_str(s).unwrap();
+
+        // then
+        assert_eq!(filter,
+                   LogsFilter {
+                       level: Some("warn".into()),
+                       target: Some("rpc".into()),
+                       offset: None,
+                       limit: Some(10),
+                   });
+    }
+
+    #[test]
+    fn should_match_level_and_target() {
+        // given
+        let filter = LogsFilter {
+            level: Some("warn".into()),
+            target: Some("rpc".into()),
+            ..Default::default()
+        };
+        let level = filter.level().unwrap();
+
+        // then
+        assert!(filter.matches(level, &entry(LogLevel::Error, "rpc")));
+        assert!(filter.matches(level, &entry(LogLevel::Warn, "rpc::ws")));
+        assert!(!filter.matches(level, &entry(LogLevel::Info, "rpc")));
+        assert!(!filter.matches(level, &entry(LogLevel::Warn, "rpcx")));
+        assert!(LogsFilter { level: Some("loud".into()), ..Default::default() }.level().is_err());
+    }
+}
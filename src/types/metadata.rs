@@ -14,14 +14,29 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use jsonrpc_core;
+use jsonrpc_pubsub::{Session, PubSubMetadata};
+use api::apis::Api;
 use types::{DappId, Origin};
 
 /// RPC methods metadata.
-#[derive(Clone, Default, Debug, PartialEq)]
+#[derive(Clone, Default, Debug)]
 pub struct Metadata {
     /// Request origin
     pub origin: Origin,
+    /// Remote address of the client (if known)
+    pub remote_addr: Option<SocketAddr>,
+    /// Subject of the TLS client certificate (if presented)
+    pub peer_subject: Option<String>,
+    /// APIs the caller is authorized to use (`None` if not restricted)
+    pub apis: Option<Arc<HashSet<Api>>>,
+    /// Whether the caller presented a valid bearer token
+    pub authenticated: bool,
+    /// Request PubSub Session
+    pub session: Option<Arc<Session>>,
 }
 
 impl Metadata {
@@ -35,3 +50,8 @@ impl Metadata {
 }
 
 impl jsonrpc_core::Metadata for Metadata {}
+impl PubSubMetadata for Metadata {
+    fn session(&self) -> Option<Arc<Session>> {
+        self.session.clone()
+    }
+}
@@ -20,9 +20,19 @@
 mod bigint;
 mod account_provider;
 mod hash;
+mod bytes;
 mod provenance;
 mod metadata;
+mod stats;
+mod logs;
+mod discover;
+mod health;
 
+pub use self::bytes::Bytes;
 pub use self::hash::{H64, H160, H256, H512, H520, H2048};
 pub use self::provenance::{Origin, DappId};
 pub use self::metadata::Metadata;
+pub use self::stats::RpcStatsInfo;
+pub use self::health::HealthStatus;
+pub use self::logs::LogsFilter;
+pub use self::discover::{DiscoverDocument, DiscoverInfo, MethodDescription, ContentDescriptor};
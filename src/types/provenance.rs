@@ -33,6 +33,9 @@ pub enum Origin {
     /// IPC server (includes session hash)
     #[serde(rename="ipc")]
     Ipc(H256),
+    /// WebSockets server (includes session hash)
+    #[serde(rename="ws")]
+    Ws(H256),
     /// Signer (includes session hash)
     #[serde(rename="signer")]
     Signer(H256),
@@ -47,12 +50,27 @@ impl Default for Origin {
     }
 }
 
+impl Origin {
+    /// Returns a short name of the transport the request came from.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Origin::Rpc(_) => "rpc",
+            Origin::Dapps(_) => "dapp",
+            Origin::Ipc(_) => "ipc",
+            Origin::Ws(_) => "ws",
+            Origin::Signer(_) => "signer",
+            Origin::Unknown => "unknown",
+        }
+    }
+}
+
 impl fmt::Display for Origin {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Origin::Rpc(ref origin) => write!(f, "RPC (service: {})", origin),
             Origin::Dapps(ref origin) => write!(f, "Dapp {}", origin),
             Origin::Ipc(ref session) => write!(f, "IPC (session: {})", session),
+            Origin::Ws(ref session) => write!(f, "WebSocket (session: {})", session),
             Origin::Signer(ref session) => write!(f, "UI (session: {})", session),
             Origin::Unknown => write!(f, "unknown origin"),
         }
@@ -112,6 +130,7 @@ mod tests {
         let o3 = Origin::Ipc(5.into());
         let o4 = Origin::Signer(10.into());
         let o5 = Origin::Unknown;
+        let o6 = Origin::Ws(15.into());
 
         // when
         let res1 = serde_json::to_string(&o1).unwrap();
@@ -119,6 +138,7 @@ mod tests {
         let res3 = serde_json::to_string(&o3).unwrap();
         let res4 = serde_json::to_string(&o4).unwrap();
         let res5 = serde_json::to_string(&o5).unwrap();
+        let res6 = serde_json::to_string(&o6).unwrap();
 
         // then
         assert_eq!(res1, r#"{"rpc":"test service"}"#);
@@ -126,6 +146,7 @@ mod tests {
         assert_eq!(res3, r#"{"ipc":"0x0000000000000000000000000000000000000000000000000000000000000005"}"#);
         assert_eq!(res4, r#"{"signer":"0x000000000000000000000000000000000000000000000000000000000000000a"}"#);
         assert_eq!(res5, r#""unknown""#);
+        assert_eq!(res6, r#"{"ws":"0x000000000000000000000000000000000000000000000000000000000000000f"}"#);
     }
 
     #[test]
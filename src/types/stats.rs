@@ -0,0 +1,63 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! RPC statistics snapshot.
+
+use util::informant::RpcStats;
+
+/// Snapshot of RPC server statistics.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct RpcStatsInfo {
+    /// Number of requests per second
+    #[serde(rename="requestsRate")]
+    pub requests_rate: usize,
+    /// Number of open sessions
+    pub sessions: usize,
+    /// Approximated roundtrip in microseconds
+    #[serde(rename="approximatedRoundtrip")]
+    pub approximated_roundtrip: u32,
+}
+
+impl<'a> From<&'a RpcStats> for RpcStatsInfo {
+    fn from(stats: &'a RpcStats) -> Self {
+        RpcStatsInfo {
+            requests_rate: stats.requests_rate(),
+            sessions: stats.sessions(),
+            approximated_roundtrip: stats.approximated_roundtrip(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use super::RpcStatsInfo;
+
+    #[test]
+    fn should_serialize_stats_info() {
+        // given
+        let info = RpcStatsInfo {
+            requests_rate: 3,
+            sessions: 1,
+            approximated_roundtrip: 125,
+        };
+
+        // when
+        let res = serde_json::to_string(&info).unwrap();
+
+        // then
+        assert_eq!(res, r#"{"requestsRate":3,"sessions":1,"approximatedRoundtrip":125}"#);
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Capture of JSON-RPC traffic for later replay.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use parking_lot::Mutex;
+use serde_json::{self, Value};
+use types::Origin;
+
+/// Single captured request with its response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureEntry {
+    /// Milliseconds since UNIX epoch when the request arrived
+    pub timestamp: u64,
+    /// Request origin
+    pub origin: Origin,
+    /// Processing time in microseconds
+    pub latency: u32,
+    /// Request as received
+    pub request: Value,
+    /// Response sent back (`None` for notifications)
+    pub response: Option<Value>,
+}
+
+/// Appends captured requests to a newline-delimited JSON file.
+pub struct Capture {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl Capture {
+    /// Opens capture file for appending, creating it if needed.
+    /// New files are readable by the owner only.
+    pub fn new(path: PathBuf) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Unable to create directory {}: {}", parent.display(), e))?;
+        }
+
+        let mut options = OpenOptions::new();
+        restrict_permissions(&mut options);
+        let file = options.create(true).append(true).open(&path)
+            .map_err(|e| format!("Unable to open capture file {}: {}", path.display(), e))?;
+
+        Ok(Capture {
+            path: path,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Returns path of the capture file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends entry to the capture file. Failures are logged, but do not affect request processing.
+    pub fn record(&self, entry: &CaptureEntry) {
+        let mut line = match serde_json::to_vec(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!(target: "rpc", "Unable to serialize captured request: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        if let Err(e) = self.file.lock().write_all(&line) {
+            warn!(target: "rpc", "Unable to write to capture file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn restrict_permissions(options: &mut OpenOptions) {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_options: &mut OpenOptions) {}
+
+/// Returns milliseconds since UNIX epoch.
+pub fn timestamp() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    now.as_secs() * 1_000 + now.subsec_nanos() as u64 / 1_000_000
+}
+
+/// Reads all entries of a capture file.
+pub fn read(path: &Path) -> Result<Vec<CaptureEntry>, String> {
+    let file = File::open(path).map_err(|e| format!("Unable to open capture file {}: {}", path.display(), e))?;
+
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|&(_, ref line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|(idx, line)| {
+            line.map_err(|e| format!("Unable to read capture file {}: {}", path.display(), e))
+                .and_then(|line| {
+                    serde_json::from_str(&line)
+                        .map_err(|e| format!("Invalid entry at {}:{}: {}", path.display(), idx + 1, e))
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use rand::random;
+    use serde_json::{self, Value};
+    use types::Origin;
+    use super::{read, Capture, CaptureEntry};
+
+    #[test]
+    fn should_append_and_read_entries() {
+        // given
+        let path = env::temp_dir().join(format!("toyrpc-capture-{}.ndjson", random::<u64>()));
+        let capture = Capture::new(path.clone()).unwrap();
+        let entry = CaptureEntry {
+            timestamp: 1_500_000_000_000,
+            origin: Origin::Rpc("curl".into()),
+            latency: 120,
+            request: json_value(r#"{"jsonrpc":"2.0","method":"net_version","params":[],"id":1}"#),
+            response: Some(json_value(r#"{"jsonrpc":"2.0","result":"1","id":1}"#)),
+        };
+
+        // when
+        capture.record(&entry);
+        capture.record(&CaptureEntry { response: None, ..entry.clone() });
+        let entries = read(&path).unwrap();
+
+        // then
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], entry);
+        assert_eq!(entries[1].response, None);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_create_file_readable_by_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // given
+        let path = env::temp_dir().join(format!("toyrpc-capture-{}.ndjson", random::<u64>()));
+
+        // when
+        Capture::new(path.clone()).unwrap();
+
+        // then
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    fn json_value(s: &str) -> Value {
+        serde_json::from_str(s).unwrap()
+    }
+}
@@ -16,21 +16,47 @@
 
 //! RPC Requests Statistics
 
+use std::cmp;
 use std::fmt;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
-use std::sync::atomic::{self, AtomicUsize};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
 use std::time;
 use futures::Future;
+use futures::sync::oneshot;
 use futures_cpupool as pool;
 use jsonrpc_core as rpc;
 use order_stat;
+use parity_reactor::Remote;
 use parking_lot::RwLock;
+use serde_json;
+use api::apis::{Api, MethodApis};
+use types::{Metadata, Origin};
+use util::capture::{self, Capture, CaptureEntry};
+use util::rate_limit::RateLimiter;
+use util::timeouts::TimeoutConfiguration;
+use util::upstream::Upstreams;
+use util::response_cache::{CacheableMethods, ResponseCache};
 
 pub use self::pool::CpuPool;
 pub use self::pool::Builder;
 
 const RATE_SECONDS: usize = 10;
 const STATS_SAMPLES: usize = 60;
+/// Error code returned when the rate limit is exceeded.
+pub const RATE_LIMIT_ERROR_CODE: i64 = -32005;
+/// Error code returned when the caller is not authorized to use a method.
+pub const UNAUTHORIZED_ERROR_CODE: i64 = -32006;
+/// Error code returned for requests arriving after shutdown has started.
+pub const SHUTDOWN_ERROR_CODE: i64 = -32007;
+/// Error code returned when the request does not finish before its deadline.
+pub const TIMEOUT_ERROR_CODE: i64 = -32008;
+/// Name under which calls of methods that are not registered are counted.
+pub const UNKNOWN_METHOD: &'static str = "unknown";
+/// Upper bounds (microseconds) of latency histogram buckets.
+pub const LATENCY_BUCKETS: [u32; 16] = [100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000,
+                                        100_000, 250_000, 500_000, 1_000_000, 2_500_000,
+                                        5_000_000, 10_000_000];
 
 struct RateCalculator {
     era: time::Instant,
@@ -136,12 +162,119 @@ impl<T: Default + Copy + Ord> StatsCalculator<T> {
     }
 }
 
+/// Latency histogram with fixed `LATENCY_BUCKETS`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Samples per bucket, last one counts samples above the highest bound.
+    counts: Vec<u64>,
+    count: u64,
+    sum: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            counts: vec![0; LATENCY_BUCKETS.len() + 1],
+            count: 0,
+            sum: 0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Add sample (microseconds)
+    pub fn add(&mut self, sample: u32) {
+        let idx = LATENCY_BUCKETS.iter().position(|bound| sample <= *bound).unwrap_or(LATENCY_BUCKETS.len());
+        self.counts[idx] += 1;
+        self.count += 1;
+        self.sum += sample as u64;
+    }
+
+    /// Returns number of samples
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns sum of all samples in microseconds
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+
+    /// Returns cumulative number of samples for each bucket upper bound.
+    pub fn buckets(&self) -> Vec<(u32, u64)> {
+        LATENCY_BUCKETS.iter()
+            .zip(self.counts.iter())
+            .scan(0, |total, (bound, count)| {
+                *total += *count;
+                Some((*bound, *total))
+            })
+            .collect()
+    }
+
+    /// Estimates given quantile (`0.0..1.0`) in microseconds,
+    /// interpolating linearly within the bucket.
+    pub fn quantile(&self, q: f64) -> u32 {
+        if self.count == 0 {
+            return 0;
+        }
+
+        let rank = q * self.count as f64;
+        let mut total = 0u64;
+        for (idx, count) in self.counts.iter().enumerate() {
+            if *count == 0 || ((total + count) as f64) < rank {
+                total += *count;
+                continue;
+            }
+
+            if idx == LATENCY_BUCKETS.len() {
+                break;
+            }
+
+            let lower = if idx == 0 { 0 } else { LATENCY_BUCKETS[idx - 1] } as f64;
+            let upper = LATENCY_BUCKETS[idx] as f64;
+            let fraction = (rank - total as f64) / *count as f64;
+            return (lower + (upper - lower) * fraction) as u32;
+        }
+
+        LATENCY_BUCKETS[LATENCY_BUCKETS.len() - 1]
+    }
+}
+
+/// Calls statistics of single method or origin.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CallStats {
+    /// Number of calls
+    pub calls: u64,
+    /// Number of calls that returned an error
+    pub errors: u64,
+    /// Calls latency
+    pub latency: Histogram,
+}
+
+impl CallStats {
+    fn add(&mut self, microseconds: u32, failed: bool) {
+        self.calls += 1;
+        if failed {
+            self.errors += 1;
+        }
+        self.latency.add(microseconds);
+    }
+}
+
 /// RPC Statistics
 #[derive(Default, Debug)]
 pub struct RpcStats {
     requests: RwLock<RateCalculator>,
     roundtrips: RwLock<StatsCalculator<u32>>,
     active_sessions: AtomicUsize,
+    total_requests: AtomicUsize,
+    rate_limited: AtomicUsize,
+    timeouts: AtomicUsize,
+    cache_hits: AtomicUsize,
+    cache_misses: AtomicUsize,
+    in_flight: AtomicUsize,
+    methods: RwLock<HashMap<String, CallStats>>,
+    origins: RwLock<HashMap<String, CallStats>>,
 }
 
 impl RpcStats {
@@ -158,6 +291,7 @@ impl RpcStats {
 
     /// Count request. Returns number of requests in current second.
     pub fn count_request(&self) -> u16 {
+        self.total_requests.fetch_add(1, atomic::Ordering::SeqCst);
         self.requests.write().tick()
     }
 
@@ -166,11 +300,78 @@ impl RpcStats {
         self.roundtrips.write().add(microseconds)
     }
 
+    /// Add finished call of given method from given origin.
+    /// Callers should group unregistered methods under `UNKNOWN_METHOD` to keep the number of entries bounded.
+    pub fn add_call(&self, method: &str, origin: &str, microseconds: u32, failed: bool) {
+        self.methods.write().entry(method.into()).or_insert_with(Default::default).add(microseconds, failed);
+        self.origins.write().entry(origin.into()).or_insert_with(Default::default).add(microseconds, failed);
+    }
+
+    /// Returns calls statistics per method
+    pub fn methods(&self) -> BTreeMap<String, CallStats> {
+        self.methods.read().iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    /// Returns calls statistics per origin
+    pub fn origins(&self) -> BTreeMap<String, CallStats> {
+        self.origins.read().iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
     /// Returns number of open sessions
     pub fn sessions(&self) -> usize {
         self.active_sessions.load(atomic::Ordering::Relaxed)
     }
 
+    /// Count request rejected by the rate limiter.
+    pub fn count_rate_limited(&self) {
+        self.rate_limited.fetch_add(1, atomic::Ordering::SeqCst);
+    }
+
+    /// Returns number of requests rejected by the rate limiter
+    pub fn rate_limited(&self) -> usize {
+        self.rate_limited.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Count request cancelled after its deadline.
+    pub fn count_timeout(&self) {
+        self.timeouts.fetch_add(1, atomic::Ordering::SeqCst);
+    }
+
+    /// Returns number of requests cancelled after their deadline
+    pub fn timeouts(&self) -> usize {
+        self.timeouts.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Count call answered from the response cache.
+    pub fn count_cache_hit(&self) {
+        self.cache_hits.fetch_add(1, atomic::Ordering::SeqCst);
+    }
+
+    /// Returns number of calls answered from the response cache
+    pub fn cache_hits(&self) -> usize {
+        self.cache_hits.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Count cacheable call which was not found in the response cache.
+    pub fn count_cache_miss(&self) {
+        self.cache_misses.fetch_add(1, atomic::Ordering::SeqCst);
+    }
+
+    /// Returns number of cacheable calls which were not found in the response cache
+    pub fn cache_misses(&self) -> usize {
+        self.cache_misses.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Returns number of requests currently being processed
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(atomic::Ordering::SeqCst)
+    }
+
+    /// Returns number of requests since start
+    pub fn requests_total(&self) -> usize {
+        self.total_requests.load(atomic::Ordering::Relaxed)
+    }
+
     /// Returns requests rate
     pub fn requests_rate(&self) -> usize {
         self.requests.read().rate()
@@ -182,10 +383,72 @@ impl RpcStats {
     }
 }
 
+/// Returns failure response for every method call within the request.
+pub fn reject(request: &rpc::Request, error: rpc::Error) -> Option<rpc::Response> {
+    let failure = |call: &rpc::Call| match *call {
+        rpc::Call::MethodCall(ref call) => Some(rpc::Output::from(Err(error.clone()), call.id.clone(), call.jsonrpc)),
+        rpc::Call::Notification(_) => None,
+        rpc::Call::Invalid(ref id) => Some(rpc::Output::from(Err(error.clone()), id.clone(), Some(rpc::Version::V2))),
+    };
+
+    match *request {
+        rpc::Request::Single(ref call) => failure(call).map(rpc::Response::Single),
+        rpc::Request::Batch(ref calls) => {
+            let outputs = calls.iter().filter_map(failure).collect::<Vec<_>>();
+            if outputs.is_empty() {
+                None
+            } else {
+                Some(rpc::Response::Batch(outputs))
+            }
+        }
+    }
+}
+
+/// Merges responses to parts of a batch.
+pub fn merge(local: Option<rpc::Response>, remote: Option<rpc::Response>) -> Option<rpc::Response> {
+    let outputs = |response: Option<rpc::Response>| match response {
+        Some(rpc::Response::Single(output)) => vec![output],
+        Some(rpc::Response::Batch(outputs)) => outputs,
+        None => Vec::new(),
+    };
+
+    let mut merged = outputs(local);
+    merged.extend(outputs(remote));
+    match merged.is_empty() {
+        true => None,
+        false => Some(rpc::Response::Batch(merged)),
+    }
+}
+
+/// Marks a request as being processed until dropped.
+pub struct InFlight(Arc<RpcStats>);
+
+impl InFlight {
+    /// Starts tracking a request in given stats.
+    pub fn new(stats: Arc<RpcStats>) -> Self {
+        stats.in_flight.fetch_add(1, atomic::Ordering::SeqCst);
+        InFlight(stats)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, atomic::Ordering::SeqCst);
+    }
+}
+
 /// Notifies about RPC activity.
 pub trait ActivityNotifier: Send + Sync + 'static {
     /// Activity on RPC interface
     fn active(&self);
+
+    /// Node is shutting down, new requests should be rejected.
+    fn shutdown(&self) {}
+
+    /// Returns true once `shutdown` was called.
+    fn is_shutdown(&self) -> bool {
+        false
+    }
 }
 
 /// Stats-counting RPC middleware
@@ -193,6 +456,13 @@ pub struct Middleware<T: ActivityNotifier = ClientNotifier> {
     stats: Arc<RpcStats>,
     notifier: T,
    	pool: Option<CpuPool>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    method_apis: Arc<MethodApis>,
+    safe_apis: Arc<HashSet<Api>>,
+    capture: Option<Arc<Capture>>,
+    timeouts: Option<(Remote, Arc<RwLock<TimeoutConfiguration>>)>,
+    upstreams: Option<Arc<Upstreams>>,
+    cache: Option<(Arc<ResponseCache>, Arc<CacheableMethods>)>,
 }
 
 impl<T: ActivityNotifier> Middleware<T> {
@@ -202,22 +472,210 @@ impl<T: ActivityNotifier> Middleware<T> {
 			stats,
 			notifier,
 			pool,
+			rate_limiter: None,
+			method_apis: Default::default(),
+			safe_apis: Default::default(),
+			capture: None,
+			timeouts: None,
+			upstreams: None,
+			cache: None,
 		}
 	}
 
+    /// Use given mapping to restrict methods to APIs allowed by request metadata.
+    pub fn with_method_apis(mut self, method_apis: MethodApis) -> Self {
+        self.method_apis = Arc::new(method_apis);
+        self
+    }
+
+    /// Returns false if any of the methods belongs to an API the caller is not authorized to use.
+    /// Methods forwarded upstream outside of the safe defaults require a valid token regardless of the transport,
+    /// only IPC is trusted through filesystem permissions.
+    fn is_authorized(&self, meta: &Metadata, calls: &[(Option<rpc::Id>, String)]) -> bool {
+        calls.iter().all(|&(_, ref method)| match self.method_apis.get(method) {
+            Some(api) => meta.apis.as_ref().map_or(true, |allowed| allowed.contains(api)),
+            None => match self.upstreams {
+                Some(ref upstreams) if upstreams.allows(method) && upstreams.requires_auth(method) => match meta.origin {
+                    Origin::Ipc(_) => true,
+                    _ => meta.authenticated,
+                },
+                _ => true,
+            },
+        })
+    }
+
+    /// Enforce rate limits on handled requests.
+    pub fn with_rate_limiter(mut self, rate_limiter: Option<Arc<RateLimiter>>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Treat given APIs as safe to capture, calls of other methods are not recorded.
+    pub fn with_safe_apis(mut self, safe_apis: HashSet<Api>) -> Self {
+        self.safe_apis = Arc::new(safe_apis);
+        self
+    }
+
+    /// Returns true if all methods belong to safe APIs, so the request carries no secrets.
+    fn is_safe(&self, calls: &[(Option<rpc::Id>, String)]) -> bool {
+        calls.iter().all(|&(_, ref method)| {
+            self.method_apis.get(method).map_or(false, |api| self.safe_apis.contains(api))
+        })
+    }
+
+    /// Record processed requests with their responses.
+    /// Requests calling methods outside of safe APIs are skipped.
+    pub fn with_capture(mut self, capture: Option<Arc<Capture>>) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// Cancel requests which do not finish before their deadline.
+    /// Deadlines can be changed while the middleware is in use.
+    pub fn with_timeouts(mut self, remote: Remote, timeouts: Arc<RwLock<TimeoutConfiguration>>) -> Self {
+        self.timeouts = Some((remote, timeouts));
+        self
+    }
+
+    /// Forward calls of unknown methods to upstream nodes.
+    pub fn with_upstreams(mut self, upstreams: Option<Arc<Upstreams>>) -> Self {
+        self.upstreams = upstreams;
+        self
+    }
+
+    /// Answer calls of given cacheable methods from the cache.
+    pub fn with_cache(mut self, cache: Option<Arc<ResponseCache>>, cacheable: CacheableMethods) -> Self {
+        self.cache = cache.map(|cache| (cache, Arc::new(cacheable)));
+        self
+    }
+
+    /// Returns key identifying the client for rate limiting purposes.
+    /// The WebSocket server does not expose peer addresses, so WebSocket sessions share a single quota
+    /// which is not reset by reconnecting.
+    fn rate_limit_key(meta: &Metadata) -> String {
+        match (&meta.origin, meta.remote_addr) {
+            (origin, Some(addr)) => format!("{}:{}", origin.kind(), addr.ip()),
+            (&Origin::Ws(_), None) => "ws".into(),
+            (origin, None) => origin.to_string(),
+        }
+    }
+
+    fn rate_limit_error(retry_after: time::Duration) -> rpc::Error {
+        let mut data = serde_json::Map::new();
+        data.insert("retryAfterMs".into(), (retry_after.as_secs()
+            .saturating_mul(1000)
+            .saturating_add(retry_after.subsec_nanos() as u64 / 1_000_000 + 1)).into());
+        rpc::Error {
+            code: rpc::ErrorCode::ServerError(RATE_LIMIT_ERROR_CODE),
+            message: "Request rate limit exceeded.".into(),
+            data: Some(rpc::Value::Object(data)),
+        }
+    }
+
+    fn timeout_error(deadline: time::Duration) -> rpc::Error {
+        let mut data = serde_json::Map::new();
+        data.insert("timeoutMs".into(), (deadline.as_secs()
+            .saturating_mul(1000)
+            .saturating_add(deadline.subsec_nanos() as u64 / 1_000_000)).into());
+        rpc::Error {
+            code: rpc::ErrorCode::ServerError(TIMEOUT_ERROR_CODE),
+            message: "Request timed out.".into(),
+            data: Some(rpc::Value::Object(data)),
+        }
+    }
+
+    /// Resolves to response returned by `on_timeout` if `future` does not finish before `deadline`.
+    /// The unfinished future is dropped then, cancelling any work still in progress.
+    fn with_timeout<F, T>(remote: &Remote, future: F, deadline: time::Duration, on_timeout: T) -> rpc::FutureResponse
+        where F: Future<Item = Option<rpc::Response>, Error = ()> + Send + 'static,
+              T: FnOnce() -> Option<rpc::Response> + Send + 'static
+    {
+        use futures::future::{self, Either};
+
+        let (expired_tx, expired_rx) = oneshot::channel();
+        // the timer is stopped as soon as `done_tx` is dropped with the request
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+        remote.spawn_with_timeout(move || done_rx.then(|_| Ok(())), deadline, move || {
+            let _ = expired_tx.send(());
+        });
+        future.select2(expired_rx).then(move |res| {
+            drop(done_tx);
+            match res {
+                Ok(Either::A((res, _))) => Either::A(future::ok(res)),
+                Ok(Either::B(((), _))) => Either::A(future::ok(on_timeout())),
+                Err(Either::A(((), _))) => Either::A(future::err(())),
+                // the timer is gone together with the event loop
+                Err(Either::B((_, future))) => Either::B(future),
+            }
+        }).boxed()
+    }
+
+    /// Records calls of a finished request, each charged an equal share of its time.
+    fn record_calls<P>(stats: &RpcStats,
+                       method_apis: &MethodApis,
+                       origin: &str,
+                       calls: Vec<(Option<rpc::Id>, String)>,
+                       elapsed: u32,
+                       failed: P)
+        where P: Fn(Option<rpc::Id>) -> bool
+    {
+        stats.add_roundtrip(elapsed);
+        let share = elapsed / cmp::max(calls.len() as u32, 1);
+        for (id, method) in calls {
+            let failed = failed(id);
+            let method = match method_apis.contains_key(&method) {
+                true => method.as_str(),
+                false => UNKNOWN_METHOD,
+            };
+            stats.add_call(method, origin, share, failed);
+        }
+    }
+
     fn as_micro(dur: time::Duration) -> u32 {
         (dur.as_secs() * 1_000_000) as u32 + dur.subsec_nanos() / 1_000
     }
+
+    /// Returns ids and names of methods called within the request.
+    fn method_calls(request: &rpc::Request) -> Vec<(Option<rpc::Id>, String)> {
+        let calls = match *request {
+            rpc::Request::Single(ref call) => vec![call],
+            rpc::Request::Batch(ref calls) => calls.iter().collect(),
+        };
+
+        calls.into_iter()
+            .filter_map(|call| match *call {
+                rpc::Call::MethodCall(ref call) => Some((Some(call.id.clone()), call.method.clone())),
+                rpc::Call::Notification(ref notification) => Some((None, notification.method.clone())),
+                rpc::Call::Invalid(_) => None,
+            })
+            .collect()
+    }
+
+    /// Returns ids of failed calls within the response.
+    fn failed_ids(response: &Option<rpc::Response>) -> Vec<rpc::Id> {
+        let outputs = match *response {
+            Some(rpc::Response::Single(ref output)) => vec![output],
+            Some(rpc::Response::Batch(ref outputs)) => outputs.iter().collect(),
+            None => Vec::new(),
+        };
+
+        outputs.into_iter()
+            .filter_map(|output| match *output {
+                rpc::Output::Failure(ref failure) => Some(failure.id.clone()),
+                rpc::Output::Success(_) => None,
+            })
+            .collect()
+    }
 }
 
-impl<M: rpc::Metadata, T: ActivityNotifier> rpc::Middleware<M> for Middleware<T> {
+impl<T: ActivityNotifier> rpc::Middleware<Metadata> for Middleware<T> {
 	type Future = rpc::futures::future::Either<
 		pool::CpuFuture<Option<rpc::Response>, ()>,
 		rpc::FutureResponse,
 	>;
 
-	fn on_request<F, X>(&self, request: rpc::Request, meta: M, process: F) -> Self::Future where
-		F: FnOnce(rpc::Request, M) -> X,
+	fn on_request<F, X>(&self, request: rpc::Request, meta: Metadata, process: F) -> Self::Future where
+		F: FnOnce(rpc::Request, Metadata) -> X,
 		X: rpc::futures::Future<Item=Option<rpc::Response>, Error=()> + Send + 'static,
 	{
 		use self::rpc::futures::future::Either::{A, B};
@@ -227,34 +685,148 @@ impl<M: rpc::Metadata, T: ActivityNotifier> rpc::Middleware<M> for Middleware<T>
 		self.notifier.active();
 		self.stats.count_request();
 
+		let calls = Self::method_calls(&request);
+		if self.notifier.is_shutdown() {
+			let error = rpc::Error {
+				code: rpc::ErrorCode::ServerError(SHUTDOWN_ERROR_CODE),
+				message: "Node is shutting down.".into(),
+				data: None,
+			};
+			return B(rpc::futures::future::ok(reject(&request, error)).boxed());
+		}
+
+		if !self.is_authorized(&meta, &calls) {
+			let error = rpc::Error {
+				code: rpc::ErrorCode::ServerError(UNAUTHORIZED_ERROR_CODE),
+				message: "Method requires authorization. Provide a valid bearer token.".into(),
+				data: None,
+			};
+			return B(rpc::futures::future::ok(reject(&request, error)).boxed());
+		}
+
+		if let Some(ref rate_limiter) = self.rate_limiter {
+			let key = Self::rate_limit_key(&meta);
+			let methods = calls.iter().map(|&(_, ref method)| method.as_str()).collect::<Vec<_>>();
+			// the whole batch is charged or rejected at once
+			if let Err(retry_after) = rate_limiter.check(&key, &methods) {
+				debug!(target: "rpc", "Rate limit exceeded for {}", key);
+				self.stats.count_rate_limited();
+				let response = reject(&request, Self::rate_limit_error(retry_after));
+				return B(rpc::futures::future::ok(response).boxed());
+			}
+		}
+
+		let deadline = self.timeouts.as_ref().and_then(|&(ref remote, ref timeouts)| {
+			timeouts.read().deadline(calls.iter().map(|&(_, ref method)| method.as_str()))
+				.map(|deadline| (remote.clone(), deadline, reject(&request, Self::timeout_error(deadline)), calls.clone()))
+		});
+
+		let origin = meta.origin.kind();
+		let captured = match self.capture {
+			Some(ref capture) if self.is_safe(&calls) => {
+				Some((capture.clone(), capture::timestamp(), meta.origin.clone(), serde_json::to_value(&request)))
+			}
+			_ => None,
+		};
 		let stats = self.stats.clone();
-		let future = process(request, meta).map(move |res| {
-			stats.add_roundtrip(Self::as_micro(start.elapsed()));
+		let method_apis = self.method_apis.clone();
+		let in_flight = InFlight::new(stats.clone());
+		let cache = self.cache.clone();
+		let process = move |request| match cache {
+			Some((cache, cacheable)) => A(cache.process(request, &cacheable, move |request| process(request, meta))),
+			None => B(process(request, meta)),
+		};
+		let processed = match self.upstreams {
+			Some(ref upstreams) => A(upstreams.route(request, &self.method_apis, process)),
+			None => B(process(request)),
+		};
+		let future = processed.map(move |res| {
+			drop(in_flight);
+			let elapsed = Self::as_micro(start.elapsed());
+			if let Some((capture, timestamp, origin, Ok(request))) = captured {
+				capture.record(&CaptureEntry {
+					timestamp: timestamp,
+					origin: origin,
+					latency: elapsed,
+					request: request,
+					response: res.as_ref().and_then(|res| serde_json::to_value(res).ok()),
+				});
+			}
+			let failed = Self::failed_ids(&res);
+			Self::record_calls(&stats, &method_apis, origin, calls, elapsed, |id| id.map_or(false, |id| failed.contains(&id)));
 			res
 		});
 
-		match self.pool {
+		let future = match self.pool {
 			Some(ref pool) => A(pool.spawn(future)),
 			None => B(future.boxed()),
+		};
+
+		match deadline {
+			Some((remote, deadline, response, calls)) => {
+				let stats = self.stats.clone();
+				let method_apis = self.method_apis.clone();
+				B(Self::with_timeout(&remote, future, deadline, move || {
+					stats.count_timeout();
+					let elapsed = Self::as_micro(start.elapsed());
+					Self::record_calls(&stats, &method_apis, origin, calls, elapsed, |_| true);
+					response
+				}))
+			}
+			None => future,
 		}
 	}
 }
 
 /// Client Notifier
+#[derive(Clone, Default)]
 pub struct ClientNotifier {
     // pub client: Arc<::ethcore::client::Client>,
+    shutdown: Arc<AtomicBool>,
 }
 
 impl ActivityNotifier for ClientNotifier {
     fn active(&self) {
         // self.client.keep_alive()
     }
+
+    fn shutdown(&self) {
+        self.shutdown.store(true, atomic::Ordering::SeqCst);
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(atomic::Ordering::SeqCst)
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::{RateCalculator, StatsCalculator, RpcStats};
+    use std::env;
+    use std::fs;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use futures::{future, Future};
+    use jsonrpc_core::{Error, MetaIoHandler, Value};
+    use parity_reactor::EventLoop;
+    use parking_lot::RwLock;
+    use rand::random;
+    use api::apis::{Api, MethodApis};
+    use types::{Metadata, Origin, H256};
+    use util::capture::{self, Capture};
+    use util::rate_limit::{RateLimiter, RateLimitConfiguration};
+    use util::timeouts::TimeoutConfiguration;
+    use super::{RateCalculator, StatsCalculator, Histogram, RpcStats, InFlight, ActivityNotifier, ClientNotifier,
+                Middleware, UNKNOWN_METHOD};
+
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
 
     #[test]
     fn should_calculate_rate() {
@@ -311,6 +883,242 @@ mod tests {
         assert_eq!(stats.approximated_roundtrip(), 125);
     }
 
+    #[test]
+    fn should_estimate_histogram_quantiles() {
+        // given
+        let mut histogram = Histogram::default();
+        for _ in 0..90 {
+            histogram.add(80);
+        }
+        for _ in 0..10 {
+            histogram.add(900);
+        }
+
+        // when
+        let p50 = histogram.quantile(0.5);
+        let p99 = histogram.quantile(0.99);
+
+        // then
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.sum(), 90 * 80 + 10 * 900);
+        assert_eq!(p50, 55);
+        assert_eq!(p99, 950);
+        assert_eq!(histogram.buckets()[0], (100, 90));
+        assert_eq!(histogram.buckets()[3], (1_000, 100));
+    }
+
+    #[test]
+    fn should_count_calls_per_method_and_origin() {
+        // given
+        let stats = RpcStats::default();
+
+        // when
+        stats.add_call("rpc_modules", "rpc", 100, false);
+        stats.add_call("rpc_modules", "ws", 200, true);
+        stats.add_call("web3_sha3", "rpc", 300, false);
+
+        // then
+        let methods = stats.methods();
+        assert_eq!(methods["rpc_modules"].calls, 2);
+        assert_eq!(methods["rpc_modules"].errors, 1);
+        assert_eq!(methods["web3_sha3"].calls, 1);
+        let origins = stats.origins();
+        assert_eq!(origins["rpc"].calls, 2);
+        assert_eq!(origins["ws"].errors, 1);
+    }
+
+    #[test]
+    fn should_group_unknown_methods_and_split_batch_time() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+        let mut methods = MethodApis::new();
+        methods.insert("hello".into(), Api::Web3);
+        let middleware = Middleware::new(stats.clone(), ClientNotifier::default(), None)
+            .with_method_apis(methods);
+        let mut io = MetaIoHandler::with_middleware(middleware);
+        io.add_method("hello", |_| Ok(Value::String("world".into())));
+
+        // when
+        io.handle_request_sync(r#"[{"jsonrpc":"2.0","method":"hello","params":[],"id":1},
+                                   {"jsonrpc":"2.0","method":"hello_0","params":[],"id":2},
+                                   {"jsonrpc":"2.0","method":"hello_1","params":[],"id":3}]"#,
+                               Metadata::default());
+
+        // then
+        let methods = stats.methods();
+        assert_eq!(methods.len(), 2);
+        assert_eq!(methods["hello"].calls, 1);
+        assert_eq!(methods["unknown"].calls, 2);
+        assert_eq!(methods["unknown"].errors, 2);
+    }
+
+    #[test]
+    fn should_reject_whole_batch_exceeding_rate_limit() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+        let limiter = RateLimiter::new(RateLimitConfiguration {
+                requests_per_second: Some(2),
+                ..Default::default()
+            })
+            .map(Arc::new);
+        let middleware = Middleware::new(stats.clone(), ClientNotifier::default(), None)
+            .with_rate_limiter(limiter);
+        let mut io = MetaIoHandler::with_middleware(middleware);
+        io.add_method("hello", |_| Ok(Value::String("world".into())));
+        let meta = Metadata { origin: Origin::Ws(Default::default()), ..Default::default() };
+
+        // when
+        let batch = io.handle_request_sync(r#"[{"jsonrpc":"2.0","method":"hello","params":[],"id":1},
+                                               {"jsonrpc":"2.0","method":"hello","params":[],"id":2},
+                                               {"jsonrpc":"2.0","method":"hello","params":[],"id":3}]"#,
+                                           meta.clone())
+            .unwrap();
+        let other_session = Metadata { origin: Origin::Ws(H256::from(1u64)), ..Default::default() };
+        let first = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"hello","params":[],"id":4}"#, meta.clone());
+        let second = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"hello","params":[],"id":5}"#, meta);
+        let third = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"hello","params":[],"id":6}"#, other_session)
+            .unwrap();
+
+        // then
+        assert_eq!(batch.matches("-32005").count(), 3, "{}", batch);
+        assert_eq!(first, Some(r#"{"jsonrpc":"2.0","result":"world","id":4}"#.into()));
+        assert_eq!(second, Some(r#"{"jsonrpc":"2.0","result":"world","id":5}"#.into()));
+        assert!(third.contains("-32005"), "{}", third);
+        assert_eq!(stats.rate_limited(), 2);
+    }
+
+    #[test]
+    fn should_track_requests_in_flight() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+
+        // when
+        let first = InFlight::new(stats.clone());
+        let second = InFlight::new(stats.clone());
+        drop(first);
+
+        // then
+        assert_eq!(stats.in_flight(), 1);
+        drop(second);
+        assert_eq!(stats.in_flight(), 0);
+    }
+
+    #[test]
+    fn should_capture_processed_requests() {
+        // given
+        let path = env::temp_dir().join(format!("toyrpc-capture-{}.ndjson", random::<u64>()));
+        let capture = Arc::new(Capture::new(path.clone()).unwrap());
+        let mut methods = MethodApis::new();
+        methods.insert("hello".into(), Api::Web3);
+        let middleware = Middleware::new(Arc::new(RpcStats::default()), ClientNotifier::default(), None)
+            .with_method_apis(methods)
+            .with_safe_apis(vec![Api::Web3].into_iter().collect())
+            .with_capture(Some(capture));
+        let mut io = MetaIoHandler::with_middleware(middleware);
+        io.add_method("hello", |_| Ok(Value::String("world".into())));
+        let meta = Metadata { origin: Origin::Rpc("curl".into()), ..Default::default() };
+
+        // when
+        io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"hello","params":[],"id":1}"#, meta.clone());
+        io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"hello","params":[]}"#, meta);
+
+        // then
+        let entries = capture::read(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].origin, Origin::Rpc("curl".into()));
+        assert_eq!(entries[0].request["method"], "hello");
+        assert_eq!(entries[0].response.as_ref().unwrap()["result"], "world");
+        assert_eq!(entries[1].response, None);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_not_capture_calls_of_unsafe_methods() {
+        // given
+        let path = env::temp_dir().join(format!("toyrpc-capture-{}.ndjson", random::<u64>()));
+        let capture = Arc::new(Capture::new(path.clone()).unwrap());
+        let mut methods = MethodApis::new();
+        methods.insert("hello".into(), Api::Web3);
+        methods.insert("unlock".into(), Api::ParityAccounts);
+        let middleware = Middleware::new(Arc::new(RpcStats::default()), ClientNotifier::default(), None)
+            .with_method_apis(methods)
+            .with_safe_apis(vec![Api::Web3].into_iter().collect())
+            .with_capture(Some(capture));
+        let mut io = MetaIoHandler::with_middleware(middleware);
+        io.add_method("hello", |_| Ok(Value::String("world".into())));
+        io.add_method("unlock", |_| Ok(Value::Bool(true)));
+
+        // when
+        io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"unlock","params":["secret"],"id":1}"#, Metadata::default());
+        io.handle_request_sync(r#"[{"jsonrpc":"2.0","method":"hello","params":[],"id":2},
+                                   {"jsonrpc":"2.0","method":"unlock","params":["secret"],"id":3}]"#,
+                               Metadata::default());
+        io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"upstream_only","params":["secret"],"id":4}"#,
+                               Metadata::default());
+        io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"hello","params":[],"id":5}"#, Metadata::default());
+
+        // then
+        let entries = capture::read(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].request["id"], 5);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_cancel_requests_after_deadline() {
+        // given
+        let event_loop = EventLoop::spawn();
+        let stats = Arc::new(RpcStats::default());
+        let mut timeouts = TimeoutConfiguration::default();
+        timeouts.methods.insert("slow".into(), Duration::from_millis(50));
+        let middleware = Middleware::new(stats.clone(), ClientNotifier::default(), None)
+            .with_timeouts(event_loop.remote(), Arc::new(RwLock::new(timeouts)));
+        let mut io = MetaIoHandler::with_middleware(middleware);
+        let dropped = Arc::new(AtomicBool::new(false));
+        let slow_dropped = dropped.clone();
+        io.add_async_method("slow", move |_| {
+            let flag = DropFlag(slow_dropped.clone());
+            future::empty::<Value, Error>().map(move |value| {
+                drop(flag);
+                value
+            }).boxed()
+        });
+        io.add_method("fast", |_| Ok(Value::Bool(true)));
+
+        // when
+        let slow = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"slow","params":[],"id":1}"#,
+                                          Metadata::default());
+        let fast = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"fast","params":[],"id":2}"#,
+                                          Metadata::default());
+
+        // then
+        assert_eq!(slow,
+                   Some(r#"{"jsonrpc":"2.0","error":{"code":-32008,"message":"Request timed out.","data":{"timeoutMs":50}},"id":1}"#.into()));
+        assert_eq!(fast, Some(r#"{"jsonrpc":"2.0","result":true,"id":2}"#.into()));
+        assert_eq!(stats.timeouts(), 1);
+        assert!(dropped.load(Ordering::SeqCst));
+        assert_eq!(stats.in_flight(), 0);
+        let methods = stats.methods();
+        assert_eq!(methods[UNKNOWN_METHOD].calls, 2);
+        assert_eq!(methods[UNKNOWN_METHOD].errors, 1);
+    }
+
+    #[test]
+    fn should_share_shutdown_state_between_notifier_clones() {
+        // given
+        let notifier = ClientNotifier::default();
+        let clone = notifier.clone();
+        assert!(!clone.is_shutdown());
+
+        // when
+        notifier.shutdown();
+
+        // then
+        assert!(clone.is_shutdown());
+    }
+
     #[test]
     fn should_be_sync_and_send() {
         let stats = RpcStats::default();
@@ -1,3 +1,9 @@
 pub mod log;
 pub mod informant;
 pub mod misc;
+pub mod subscription_manager;
+pub mod rate_limit;
+pub mod capture;
+pub mod timeouts;
+pub mod upstream;
+pub mod response_cache;
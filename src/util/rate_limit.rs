@@ -0,0 +1,310 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Token-bucket rate limiting of RPC calls.
+
+use std::collections::BTreeMap;
+use std::cmp;
+use std::time::{Duration, Instant};
+use lru_cache::LruCache;
+use parking_lot::{Mutex, RwLock};
+
+/// Maximal number of buckets kept, least recently used ones are evicted first.
+const MAX_BUCKETS: usize = 4096;
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RateLimitConfiguration {
+    /// Calls per second allowed for every origin (all methods).
+    pub requests_per_second: Option<u32>,
+    /// Maximal number of calls allowed in a single burst.
+    pub burst: Option<u32>,
+    /// Calls per second allowed for every origin per method.
+    pub methods: BTreeMap<String, u32>,
+}
+
+impl RateLimitConfiguration {
+    /// Parses `method=rate` pairs. Rates have to be positive.
+    pub fn parse_methods(list: &[String]) -> Result<BTreeMap<String, u32>, String> {
+        list.iter()
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                let mut parts = item.splitn(2, '=');
+                match (parts.next(), parts.next().and_then(|rate| rate.parse().ok())) {
+                    (Some(method), Some(rate)) if rate > 0 => Ok((method.to_owned(), rate)),
+                    _ => Err(format!("Invalid method rate limit given: {}. Expected method=rate with positive rate.", item)),
+                }
+            })
+            .collect()
+    }
+
+    fn enabled(&self) -> bool {
+        self.requests_per_second.is_some() || !self.methods.is_empty()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    rate: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u32, burst: Option<u32>) -> Self {
+        let capacity = cmp::max(rate, burst.unwrap_or(rate)) as f64;
+        Bucket {
+            tokens: capacity,
+            capacity: capacity,
+            rate: rate as f64,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last);
+        let elapsed = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000f64;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last = now;
+    }
+
+    /// Returns time after which `count` tokens will be available, `None` if they are available now.
+    /// Requests for more tokens than the bucket holds are never satisfied.
+    fn wait(&mut self, now: Instant, count: u32) -> Option<Duration> {
+        self.refill(now);
+        let missing = count as f64 - self.tokens;
+        if missing <= 0f64 {
+            return None;
+        }
+
+        if self.rate <= 0f64 {
+            return Some(Duration::from_secs(u64::max_value()));
+        }
+
+        let wait = missing / self.rate;
+        Some(Duration::new(wait as u64, (wait.fract() * 1_000_000_000f64) as u32))
+    }
+}
+
+/// Client key and optionally the method the bucket limits.
+type BucketKey = (String, Option<String>);
+
+/// Returns the bucket under given key, creating it if it does not exist.
+fn bucket<'a>(buckets: &'a mut LruCache<BucketKey, Bucket>,
+              key: &BucketKey,
+              rate: u32,
+              burst: Option<u32>)
+              -> &'a mut Bucket {
+    if !buckets.contains_key(key) {
+        buckets.insert(key.clone(), Bucket::new(rate, burst));
+    }
+    buckets.get_mut(key).expect("Bucket inserted above; qed")
+}
+
+/// Per-origin token-bucket rate limiter.
+pub struct RateLimiter {
+    config: RwLock<RateLimitConfiguration>,
+    buckets: Mutex<LruCache<BucketKey, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Creates new `RateLimiter`, returns `None` if no limits are configured.
+    pub fn new(config: RateLimitConfiguration) -> Option<Self> {
+        if !config.enabled() {
+            return None;
+        }
+
+        Some(RateLimiter {
+            config: RwLock::new(config),
+            buckets: Mutex::new(LruCache::new(MAX_BUCKETS)),
+        })
+    }
+
+    /// Replaces configured limits. Quotas used so far are forgotten.
+    pub fn reconfigure(&self, config: RateLimitConfiguration) {
+        *self.config.write() = config;
+        self.buckets.lock().clear();
+    }
+
+    /// Takes a token for every call of given methods made by client identified with `key`.
+    /// Calls are charged together: if the quota of any of them is exceeded, no tokens are taken
+    /// and time to wait before retrying is returned.
+    pub fn check(&self, key: &str, methods: &[&str]) -> Result<(), Duration> {
+        let now = Instant::now();
+        let config = self.config.read();
+        let mut buckets = self.buckets.lock();
+
+        let mut demands = Vec::new();
+        if let Some(rate) = config.requests_per_second {
+            demands.push(((key.to_owned(), None), rate, methods.len() as u32));
+        }
+        let mut per_method = BTreeMap::new();
+        for method in methods {
+            if let Some(rate) = config.methods.get(*method) {
+                per_method.entry(*method).or_insert((*rate, 0)).1 += 1;
+            }
+        }
+        demands.extend(per_method.into_iter()
+            .map(|(method, (rate, count))| ((key.to_owned(), Some(method.to_owned())), rate, count)));
+
+        let wait = demands.iter()
+            .filter_map(|&(ref key, rate, count)| bucket(&mut buckets, key, rate, config.burst).wait(now, count))
+            .max();
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+
+        for (key, rate, count) in demands {
+            bucket(&mut buckets, &key, rate, config.burst).tokens -= count as f64;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::{RateLimiter, RateLimitConfiguration, MAX_BUCKETS};
+
+    #[test]
+    fn should_be_disabled_without_limits() {
+        assert!(RateLimiter::new(RateLimitConfiguration::default()).is_none());
+    }
+
+    #[test]
+    fn should_limit_calls_per_origin() {
+        // given
+        let limiter = RateLimiter::new(RateLimitConfiguration {
+                requests_per_second: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+
+        // when
+        let first = limiter.check("a", &["web3_sha3"]);
+        let second = limiter.check("a", &["rpc_modules"]);
+        let third = limiter.check("a", &["web3_sha3"]);
+        let other = limiter.check("b", &["web3_sha3"]);
+
+        // then
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        assert!(third.unwrap_err().as_secs() < 1);
+        assert!(other.is_ok());
+    }
+
+    #[test]
+    fn should_limit_calls_per_method() {
+        // given
+        let mut methods = BTreeMap::new();
+        methods.insert("web3_sha3".to_owned(), 1);
+        let limiter = RateLimiter::new(RateLimitConfiguration {
+                methods: methods,
+                ..Default::default()
+            })
+            .unwrap();
+
+        // when
+        let first = limiter.check("a", &["web3_sha3"]);
+        let second = limiter.check("a", &["web3_sha3"]);
+        let other = limiter.check("a", &["rpc_modules"]);
+
+        // then
+        assert!(first.is_ok());
+        assert!(second.is_err());
+        assert!(other.is_ok());
+    }
+
+    #[test]
+    fn should_apply_new_limits_after_reconfigure() {
+        // given
+        let limiter = RateLimiter::new(RateLimitConfiguration {
+                requests_per_second: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(limiter.check("a", &["web3_sha3"]).is_ok());
+        assert!(limiter.check("a", &["web3_sha3"]).is_err());
+
+        // when
+        limiter.reconfigure(RateLimitConfiguration {
+            requests_per_second: Some(2),
+            ..Default::default()
+        });
+
+        // then
+        assert!(limiter.check("a", &["web3_sha3"]).is_ok());
+        assert!(limiter.check("a", &["web3_sha3"]).is_ok());
+        assert!(limiter.check("a", &["web3_sha3"]).is_err());
+    }
+
+    #[test]
+    fn should_parse_method_limits() {
+        // given
+        let list = vec!["web3_sha3=5".to_owned(), "rpc_modules=1".to_owned()];
+
+        // when
+        let methods = RateLimitConfiguration::parse_methods(&list).unwrap();
+
+        // then
+        assert_eq!(methods["web3_sha3"], 5);
+        assert_eq!(methods["rpc_modules"], 1);
+        assert!(RateLimitConfiguration::parse_methods(&["web3_sha3".to_owned()]).is_err());
+        assert!(RateLimitConfiguration::parse_methods(&["web3_sha3=0".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn should_keep_bounded_number_of_buckets() {
+        // given
+        let limiter = RateLimiter::new(RateLimitConfiguration {
+                requests_per_second: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(limiter.check("a", &["web3_sha3"]).is_ok());
+        assert!(limiter.check("a", &["web3_sha3"]).is_err());
+
+        // when
+        for client in 0..MAX_BUCKETS * 2 {
+            assert!(limiter.check(&client.to_string(), &["web3_sha3"]).is_ok());
+        }
+
+        // then
+        assert_eq!(limiter.buckets.lock().len(), MAX_BUCKETS);
+        assert!(limiter.check("a", &["web3_sha3"]).is_ok());
+    }
+
+    #[test]
+    fn should_charge_batch_atomically() {
+        // given
+        let mut methods = BTreeMap::new();
+        methods.insert("web3_sha3".to_owned(), 2);
+        let limiter = RateLimiter::new(RateLimitConfiguration {
+                requests_per_second: Some(3),
+                methods: methods,
+                ..Default::default()
+            })
+            .unwrap();
+
+        // when
+        let too_many = limiter.check("a", &["web3_sha3", "web3_sha3", "web3_sha3"]);
+        let batch = limiter.check("a", &["web3_sha3", "web3_sha3", "rpc_modules"]);
+        let exhausted = limiter.check("a", &["rpc_modules"]);
+
+        // then
+        assert!(too_many.is_err());
+        assert!(batch.is_ok());
+        assert!(exhausted.is_err());
+    }
+}
@@ -0,0 +1,394 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Caching of responses to idempotent RPC calls.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use ethcore_util::cache::MemoryLruCache;
+use futures::{future, Future};
+use heapsize::HeapSizeOf;
+use jsonrpc_core as rpc;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::{self, Value};
+use util::informant::{RpcStats, merge};
+
+/// Methods which may be answered from the cache with time their results stay valid.
+pub type CacheableMethods = HashMap<String, Duration>;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CacheConfiguration {
+    /// Whether responses are cached at all.
+    pub enabled: bool,
+    /// Memory budget of cached responses in bytes.
+    pub size: usize,
+}
+
+impl Default for CacheConfiguration {
+    fn default() -> Self {
+        CacheConfiguration {
+            enabled: false,
+            size: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Built-in methods which are safe to cache, i.e. their results do not depend on the caller.
+pub fn builtin(method: &str) -> Option<Duration> {
+    match method {
+        "web3_clientVersion" | "web3_sha3" => Some(Duration::from_secs(3600)),
+        "net_version" => Some(Duration::from_secs(60)),
+        _ => None,
+    }
+}
+
+/// Method name and canonicalised params.
+type Key = (String, String);
+
+struct Entry {
+    result: Value,
+    size: usize,
+    expires: Instant,
+}
+
+impl HeapSizeOf for Entry {
+    fn heap_size_of_children(&self) -> usize {
+        self.size
+    }
+}
+
+/// Memory-bounded cache of call results shared by all RPC servers.
+pub struct ResponseCache {
+    size: AtomicUsize,
+    entries: Arc<Mutex<MemoryLruCache<Key, Entry>>>,
+    stats: Arc<RpcStats>,
+}
+
+impl ResponseCache {
+    /// Creates new cache, returns `None` if caching is disabled.
+    pub fn new(conf: CacheConfiguration, stats: Arc<RpcStats>) -> Option<Self> {
+        if !conf.enabled {
+            return None;
+        }
+
+        Some(ResponseCache {
+            size: AtomicUsize::new(conf.size),
+            entries: Arc::new(Mutex::new(MemoryLruCache::new(conf.size))),
+            stats: stats,
+        })
+    }
+
+    /// Removes all cached responses.
+    pub fn flush(&self) {
+        *self.entries.lock() = MemoryLruCache::new(self.size.load(Ordering::SeqCst));
+    }
+
+    /// Changes memory budget of cached responses, removing all of them.
+    pub fn resize(&self, size: usize) {
+        self.size.store(size, Ordering::SeqCst);
+        self.flush();
+    }
+
+    /// Returns memory used by cached responses in bytes.
+    pub fn current_size(&self) -> usize {
+        self.entries.lock().current_size()
+    }
+
+    /// Answers calls of cacheable methods from the cache and processes the remaining ones,
+    /// caching their successful results.
+    pub fn process<F, X>(&self, request: rpc::Request, cacheable: &CacheableMethods, process: F) -> rpc::FutureResponse
+        where F: FnOnce(rpc::Request) -> X,
+              X: Future<Item = Option<rpc::Response>, Error = ()> + Send + 'static
+    {
+        let (single, calls) = match request {
+            rpc::Request::Single(call) => (true, vec![call]),
+            rpc::Request::Batch(calls) => (false, calls),
+        };
+
+        let now = Instant::now();
+        let mut hits = Vec::new();
+        let mut misses = Vec::new();
+        let mut pending = HashMap::new();
+        {
+            let mut entries = self.entries.lock();
+            for call in calls {
+                let key = match call {
+                    rpc::Call::MethodCall(ref call) if cacheable.contains_key(&call.method) => {
+                        Some((call.id.clone(), call.jsonrpc, (call.method.clone(), canonical(&call.params))))
+                    }
+                    _ => None,
+                };
+
+                match key {
+                    Some((id, jsonrpc, key)) => {
+                        match entries.get_mut(&key) {
+                            Some(ref entry) if entry.expires > now => {
+                                self.stats.count_cache_hit();
+                                hits.push(rpc::Output::from(Ok(entry.result.clone()), id, jsonrpc));
+                            }
+                            _ => {
+                                self.stats.count_cache_miss();
+                                let ttl = cacheable[&key.0];
+                                pending.insert(id, (key, ttl));
+                                misses.push(call);
+                            }
+                        }
+                    }
+                    None => misses.push(call),
+                }
+            }
+        }
+
+        // results are matched to calls by id, which is ambiguous if ids repeat
+        let mut ids = HashSet::new();
+        let repeated = misses.iter()
+            .filter_map(|call| match *call {
+                rpc::Call::MethodCall(ref call) => Some(call.id.clone()),
+                rpc::Call::Invalid(ref id) => Some(id.clone()),
+                rpc::Call::Notification(_) => None,
+            })
+            .filter(|id| !ids.insert(id.clone()))
+            .collect::<Vec<_>>();
+        for id in repeated {
+            pending.remove(&id);
+        }
+
+        if misses.is_empty() {
+            let response = match single {
+                true => hits.pop().map(rpc::Response::Single),
+                false => Some(rpc::Response::Batch(hits)),
+            };
+            return future::ok(response).boxed();
+        }
+
+        let request = match single {
+            true => rpc::Request::Single(misses.remove(0)),
+            false => rpc::Request::Batch(misses),
+        };
+        let entries = self.entries.clone();
+        process(request)
+            .map(move |response| {
+                store(&entries, &response, pending);
+                match hits.is_empty() {
+                    true => response,
+                    false => merge(Some(rpc::Response::Batch(hits)), response),
+                }
+            })
+            .boxed()
+    }
+}
+
+/// Caches successful results of pending calls found in the response.
+fn store(entries: &Mutex<MemoryLruCache<Key, Entry>>, response: &Option<rpc::Response>, mut pending: HashMap<rpc::Id, (Key, Duration)>) {
+    if pending.is_empty() {
+        return;
+    }
+
+    let outputs = match *response {
+        Some(rpc::Response::Single(ref output)) => vec![output],
+        Some(rpc::Response::Batch(ref outputs)) => outputs.iter().collect(),
+        None => Vec::new(),
+    };
+
+    let now = Instant::now();
+    let mut entries = entries.lock();
+    for output in outputs {
+        if let rpc::Output::Success(ref success) = *output {
+            if let Some((key, ttl)) = pending.remove(&success.id) {
+                let size = key.0.len() + key.1.len() + serde_json::to_string(&success.result).map(|s| s.len()).unwrap_or(0);
+                entries.insert(key, Entry {
+                    result: success.result.clone(),
+                    size: size,
+                    expires: now + ttl,
+                });
+            }
+        }
+    }
+}
+
+/// Serializes params with object keys sorted, so equivalent params produce the same key.
+fn canonical<T: Serialize>(params: &T) -> String {
+    fn write(value: &Value, out: &mut String) {
+        match *value {
+            Value::Array(ref values) => {
+                out.push('[');
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        out.push(',');
+                    }
+                    write(value, out);
+                }
+                out.push(']');
+            }
+            Value::Object(ref map) => {
+                let mut keys = map.keys().collect::<Vec<_>>();
+                keys.sort();
+                out.push('{');
+                for (idx, key) in keys.into_iter().enumerate() {
+                    if idx > 0 {
+                        out.push(',');
+                    }
+                    let _ = write!(out, "{}:", Value::String(key.clone()));
+                    write(&map[key], out);
+                }
+                out.push('}');
+            }
+            ref value => {
+                let _ = write!(out, "{}", value);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    if let Ok(value) = serde_json::to_value(params) {
+        write(&value, &mut out);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use jsonrpc_core::{MetaIoHandler, Value};
+    use serde_json;
+    use impls::Web3Client;
+    use traits::Web3;
+    use types::Metadata;
+    use util::informant::{RpcStats, ClientNotifier, Middleware};
+    use super::{CacheableMethods, CacheConfiguration, ResponseCache};
+
+    fn io(ttl: Duration) -> (MetaIoHandler<Metadata, Middleware>, Arc<ResponseCache>, Arc<RpcStats>, Arc<AtomicUsize>) {
+        let stats = Arc::new(RpcStats::default());
+        let conf = CacheConfiguration {
+            enabled: true,
+            ..Default::default()
+        };
+        let cache = Arc::new(ResponseCache::new(conf, stats.clone()).unwrap());
+        let mut cacheable = CacheableMethods::new();
+        cacheable.insert("count".into(), ttl);
+        let middleware = Middleware::new(stats.clone(), ClientNotifier::default(), None)
+            .with_cache(Some(cache.clone()), cacheable);
+        let mut io = MetaIoHandler::with_middleware(middleware);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        io.add_method("count", move |_| Ok(Value::from(counter.fetch_add(1, Ordering::SeqCst) + 1)));
+        let counter = calls.clone();
+        io.add_method("uncached", move |_| Ok(Value::from(counter.fetch_add(1, Ordering::SeqCst) + 1)));
+        (io, cache, stats, calls)
+    }
+
+    #[test]
+    fn should_answer_repeated_calls_from_cache() {
+        // given
+        let (io, cache, stats, calls) = io(Duration::from_secs(60));
+        let first = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"count","params":{"a":1,"b":2},"id":1}"#,
+                                           Metadata::default());
+
+        // when
+        let second = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"count","params":{"b":2,"a":1},"id":2}"#,
+                                            Metadata::default());
+        let other = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"count","params":{"a":2},"id":3}"#,
+                                           Metadata::default());
+
+        // then
+        assert_eq!(first, Some(r#"{"jsonrpc":"2.0","result":1,"id":1}"#.into()));
+        assert_eq!(second, Some(r#"{"jsonrpc":"2.0","result":1,"id":2}"#.into()));
+        assert_eq!(other, Some(r#"{"jsonrpc":"2.0","result":2,"id":3}"#.into()));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(stats.cache_hits(), 1);
+        assert_eq!(stats.cache_misses(), 2);
+        assert!(cache.current_size() > 0);
+    }
+
+    #[test]
+    fn should_process_uncached_calls_of_a_batch() {
+        // given
+        let (io, _, _, calls) = io(Duration::from_secs(60));
+        io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"count","params":[],"id":1}"#, Metadata::default());
+
+        // when
+        let res = io.handle_request_sync(r#"[{"jsonrpc":"2.0","method":"count","params":[],"id":2},
+                                             {"jsonrpc":"2.0","method":"uncached","params":[],"id":3}]"#,
+                                         Metadata::default());
+
+        // then
+        assert_eq!(res, Some(r#"[{"jsonrpc":"2.0","result":1,"id":2},{"jsonrpc":"2.0","result":2,"id":3}]"#.into()));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn should_not_cache_calls_with_repeated_ids() {
+        // given
+        let stats = Arc::new(RpcStats::default());
+        let conf = CacheConfiguration {
+            enabled: true,
+            ..Default::default()
+        };
+        let cache = Arc::new(ResponseCache::new(conf, stats.clone()).unwrap());
+        let mut cacheable = CacheableMethods::new();
+        cacheable.insert("web3_sha3".into(), Duration::from_secs(60));
+        let middleware = Middleware::new(stats, ClientNotifier::default(), None)
+            .with_cache(Some(cache), cacheable);
+        let mut io = MetaIoHandler::with_middleware(middleware);
+        io.extend_with(Web3Client::new().to_delegate());
+        let result = |response: Option<String>| -> Value {
+            serde_json::from_str::<Value>(&response.unwrap()).unwrap()["result"].clone()
+        };
+
+        // when
+        let batch = io.handle_request_sync(r#"[{"jsonrpc":"2.0","method":"web3_sha3","params":["0x01"],"id":1},
+                                               {"jsonrpc":"2.0","method":"web3_sha3","params":["0x02"],"id":1}]"#,
+                                           Metadata::default());
+        let first = result(io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"web3_sha3","params":["0x01"],"id":2}"#,
+                                                  Metadata::default()));
+        let second = result(io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"web3_sha3","params":["0x02"],"id":3}"#,
+                                                   Metadata::default()));
+
+        // then
+        let batch: Value = serde_json::from_str(&batch.unwrap()).unwrap();
+        assert_eq!(batch[0]["result"], first);
+        assert_eq!(batch[1]["result"], second);
+        assert!(first != second);
+    }
+
+    #[test]
+    fn should_not_answer_expired_or_flushed_calls() {
+        // given
+        let (expiring, _, _, expiring_calls) = io(Duration::from_secs(0));
+        let (io, cache, _, calls) = io(Duration::from_secs(60));
+        let request = r#"{"jsonrpc":"2.0","method":"count","params":[],"id":1}"#;
+        expiring.handle_request_sync(request, Metadata::default());
+        io.handle_request_sync(request, Metadata::default());
+
+        // when
+        let expired = expiring.handle_request_sync(request, Metadata::default());
+        cache.flush();
+        let size = cache.current_size();
+        let flushed = io.handle_request_sync(request, Metadata::default());
+
+        // then
+        assert_eq!(expired, Some(r#"{"jsonrpc":"2.0","result":2,"id":1}"#.into()));
+        assert_eq!(flushed, Some(r#"{"jsonrpc":"2.0","result":2,"id":1}"#.into()));
+        assert_eq!(expiring_calls.load(Ordering::SeqCst), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(size, 0);
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Server-push subscriptions management.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use futures::{future, Future, BoxFuture};
+use futures::future::{Either, Loop};
+use jsonrpc_core::Value;
+use jsonrpc_macros::pubsub::{Subscriber, Sink};
+use jsonrpc_pubsub::{Session, SubscriptionId};
+use parity_reactor::{TokioRemote, Timeout};
+use parking_lot::{Mutex, RwLock};
+use rand;
+use serde_json;
+use types::RpcStatsInfo;
+use util::informant::RpcStats;
+
+const STATS_INTERVAL_SECONDS: u64 = 5;
+
+/// Subscription topic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Topic {
+    /// Periodic `RpcStats` snapshots
+    Stats,
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stats" => Ok(Topic::Stats),
+            topic => Err(format!("Unknown topic: {}", topic)),
+        }
+    }
+}
+
+struct Subscription {
+    topic: Topic,
+    sink: Sink<Value>,
+    session: usize,
+}
+
+/// Returns address of the session, unique while a weak reference to it is kept.
+fn session_address(session: &Arc<Session>) -> usize {
+    &**session as *const Session as usize
+}
+
+type Subscriptions = Arc<RwLock<HashMap<SubscriptionId, Subscription>>>;
+/// Ids of sessions with a registered drop hook, by session address.
+/// Weak references keep the addresses from being reused while the entries exist.
+type Sessions = Arc<Mutex<HashMap<usize, (Weak<Session>, usize)>>>;
+
+/// Keeps track of active subscriptions and delivers notifications to them.
+pub struct SubscriptionManager {
+    stats: Arc<RpcStats>,
+    subscriptions: Subscriptions,
+    sessions: Sessions,
+    next_session: AtomicUsize,
+}
+
+impl SubscriptionManager {
+    /// Creates new `SubscriptionManager` publishing given stats.
+    pub fn new(stats: Arc<RpcStats>) -> Self {
+        SubscriptionManager {
+            stats: stats,
+            subscriptions: Default::default(),
+            sessions: Default::default(),
+            next_session: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns id of the session if it has any subscriptions.
+    fn session_id(&self, session: &Arc<Session>) -> Option<usize> {
+        self.sessions.lock().get(&session_address(session)).map(|&(_, id)| id)
+    }
+
+    /// Returns id of the session, assigning a new one on first use.
+    /// Subscriptions of the session are removed as soon as it's closed.
+    fn register_session(&self, session: &Arc<Session>) -> usize {
+        let address = session_address(session);
+        let mut sessions = self.sessions.lock();
+        if let Some(&(_, id)) = sessions.get(&address) {
+            return id;
+        }
+
+        let id = self.next_session.fetch_add(1, Ordering::SeqCst);
+        sessions.insert(address, (Arc::downgrade(session), id));
+        let subscriptions = Arc::downgrade(&self.subscriptions);
+        let known_sessions = Arc::downgrade(&self.sessions);
+        session.on_drop(move || {
+            if let Some(subscriptions) = subscriptions.upgrade() {
+                subscriptions.write().retain(|_, subscription| subscription.session != id);
+            }
+            if let Some(sessions) = known_sessions.upgrade() {
+                sessions.lock().remove(&address);
+            }
+        });
+        id
+    }
+
+    /// Assigns an id to new subscriber.
+    /// Subscription is removed as soon as the session is closed.
+    pub fn add(&self, session: Arc<Session>, topic: Topic, subscriber: Subscriber<Value>) {
+        let id = SubscriptionId::String(format!("0x{:016x}", rand::random::<u64>()));
+        if let Ok(sink) = subscriber.assign_id(id.clone()) {
+            let owner = self.register_session(&session);
+            self.subscriptions.write().insert(id,
+                                              Subscription {
+                                                  topic: topic,
+                                                  sink: sink,
+                                                  session: owner,
+                                              });
+        }
+    }
+
+    /// Removes subscription created within given session.
+    /// Returns false if subscription was not found or belongs to another session.
+    pub fn remove(&self, session: &Arc<Session>, id: &SubscriptionId) -> bool {
+        let owner = match self.session_id(session) {
+            Some(owner) => owner,
+            None => return false,
+        };
+        let mut subscriptions = self.subscriptions.write();
+        let owned = subscriptions.get(id).map_or(false, |subscription| subscription.session == owner);
+        if owned {
+            subscriptions.remove(id);
+        }
+        owned
+    }
+
+    /// Returns number of active subscriptions
+    pub fn subscriptions(&self) -> usize {
+        self.subscriptions.read().len()
+    }
+
+    /// Sends current stats snapshot to every `Topic::Stats` subscriber.
+    pub fn notify_stats(&self) -> BoxFuture<(), ()> {
+        let info = RpcStatsInfo::from(&*self.stats);
+        let value = serde_json::to_value(info).expect("RpcStatsInfo serialization is infallible; qed");
+        let notifications = self.subscriptions
+            .read()
+            .values()
+            .filter(|subscription| subscription.topic == Topic::Stats)
+            .map(|subscription| subscription.sink.notify(Ok(value.clone())).then(|_| Ok::<_, ()>(())))
+            .collect::<Vec<_>>();
+
+        future::join_all(notifications).map(|_| ()).boxed()
+    }
+
+    /// Spawns a timer on the event loop that periodically publishes stats snapshots.
+    /// Timer stops when the manager is dropped.
+    pub fn start(manager: &Arc<Self>, remote: &TokioRemote) {
+        let manager = Arc::downgrade(manager);
+        remote.spawn(move |handle| {
+            let handle = handle.clone();
+            future::loop_fn((), move |_| {
+                let manager = manager.clone();
+                Timeout::new(Duration::from_secs(STATS_INTERVAL_SECONDS), &handle)
+                    .expect("Event loop is still up.")
+                    .map_err(|_| ())
+                    .and_then(move |_| match manager.upgrade() {
+                        Some(manager) => Either::A(manager.notify_stats().map(|_| Loop::Continue(()))),
+                        None => Either::B(future::ok(Loop::Break(()))),
+                    })
+            })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use futures::{Future, Stream};
+    use futures::sync::mpsc;
+    use jsonrpc_core::MetaIoHandler;
+    use jsonrpc_pubsub::Session;
+    use impls::PubSubClient;
+    use types::Metadata;
+    use util::informant::RpcStats;
+    use super::SubscriptionManager;
+
+    #[test]
+    fn should_notify_stats_subscribers_and_cleanup_on_disconnect() {
+        // given
+        let manager = Arc::new(SubscriptionManager::new(Arc::new(RpcStats::default())));
+        let mut handler = MetaIoHandler::<Metadata>::default();
+        handler.extend_with(PubSubClient::new(manager.clone()).into_delegate());
+        let (tx, rx) = mpsc::channel(8);
+        let mut meta = Metadata::default();
+        meta.session = Some(Arc::new(Session::new(tx)));
+
+        // when
+        let request = r#"{"jsonrpc":"2.0","method":"pubsub_subscribe","params":["stats"],"id":1}"#;
+        let response = handler.handle_request_sync(request, meta.clone()).unwrap();
+        manager.notify_stats().wait().unwrap();
+
+        // then
+        assert!(response.contains(r#""result":"0x"#));
+        assert_eq!(manager.subscriptions(), 1);
+        let (notification, _) = rx.into_future().wait().ok().unwrap();
+        assert!(notification.unwrap().contains(r#""method":"pubsub_subscription""#));
+
+        // when
+        drop(meta);
+
+        // then
+        assert_eq!(manager.subscriptions(), 0);
+    }
+
+    #[test]
+    fn should_reject_unknown_topic() {
+        // given
+        let manager = Arc::new(SubscriptionManager::new(Arc::new(RpcStats::default())));
+        let mut handler = MetaIoHandler::<Metadata>::default();
+        handler.extend_with(PubSubClient::new(manager.clone()).into_delegate());
+        let (tx, _rx) = mpsc::channel(8);
+        let mut meta = Metadata::default();
+        meta.session = Some(Arc::new(Session::new(tx)));
+
+        // when
+        let request = r#"{"jsonrpc":"2.0","method":"pubsub_subscribe","params":["blocks"],"id":1}"#;
+        let response = handler.handle_request_sync(request, meta).unwrap();
+
+        // then
+        assert!(response.contains("Unknown topic: blocks"));
+        assert_eq!(manager.subscriptions(), 0);
+    }
+
+    #[test]
+    fn should_not_unsubscribe_from_other_session() {
+        // given
+        let manager = Arc::new(SubscriptionManager::new(Arc::new(RpcStats::default())));
+        let mut handler = MetaIoHandler::<Metadata>::default();
+        handler.extend_with(PubSubClient::new(manager.clone()).into_delegate());
+        let (tx1, _rx1) = mpsc::channel(8);
+        let (tx2, _rx2) = mpsc::channel(8);
+        let mut owner = Metadata::default();
+        owner.session = Some(Arc::new(Session::new(tx1)));
+        let mut other = Metadata::default();
+        other.session = Some(Arc::new(Session::new(tx2)));
+        let request = r#"{"jsonrpc":"2.0","method":"pubsub_subscribe","params":["stats"],"id":1}"#;
+        let response = handler.handle_request_sync(request, owner.clone()).unwrap();
+        let id = response.split(r#""result":""#).nth(1).unwrap().split('"').next().unwrap().to_owned();
+        let unsubscribe = format!(r#"{{"jsonrpc":"2.0","method":"pubsub_unsubscribe","params":["{}"],"id":2}}"#, id);
+
+        // when
+        let res1 = handler.handle_request_sync(&unsubscribe, other.clone()).unwrap();
+        let subscriptions = manager.subscriptions();
+        let res2 = handler.handle_request_sync(&unsubscribe, owner.clone()).unwrap();
+
+        // then
+        assert_eq!(res1, r#"{"jsonrpc":"2.0","result":false,"id":2}"#);
+        assert_eq!(subscriptions, 1);
+        assert_eq!(res2, r#"{"jsonrpc":"2.0","result":true,"id":2}"#);
+        assert_eq!(manager.subscriptions(), 0);
+    }
+
+    #[test]
+    fn should_register_session_once_and_forget_it_on_disconnect() {
+        // given
+        let manager = Arc::new(SubscriptionManager::new(Arc::new(RpcStats::default())));
+        let mut handler = MetaIoHandler::<Metadata>::default();
+        handler.extend_with(PubSubClient::new(manager.clone()).into_delegate());
+        let (tx, _rx) = mpsc::channel(8);
+        let mut meta = Metadata::default();
+        meta.session = Some(Arc::new(Session::new(tx)));
+        let request = r#"{"jsonrpc":"2.0","method":"pubsub_subscribe","params":["stats"],"id":1}"#;
+
+        // when
+        for _ in 0..3 {
+            let response = handler.handle_request_sync(request, meta.clone()).unwrap();
+            let id = response.split(r#""result":""#).nth(1).unwrap().split('"').next().unwrap().to_owned();
+            let unsubscribe = format!(r#"{{"jsonrpc":"2.0","method":"pubsub_unsubscribe","params":["{}"],"id":2}}"#, id);
+            handler.handle_request_sync(&unsubscribe, meta.clone()).unwrap();
+        }
+        handler.handle_request_sync(request, meta.clone()).unwrap();
+
+        // then
+        assert_eq!(manager.sessions.lock().len(), 1);
+        assert_eq!(manager.subscriptions(), 1);
+
+        // when
+        drop(meta);
+
+        // then
+        assert_eq!(manager.sessions.lock().len(), 0);
+        assert_eq!(manager.subscriptions(), 0);
+    }
+}
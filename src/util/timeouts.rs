@@ -0,0 +1,118 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Deadlines of RPC requests.
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use helpers::to_duration;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimeoutConfiguration {
+    /// Deadline of every request (`None` if requests may run forever).
+    pub request: Option<Duration>,
+    /// Deadlines of particular methods, overriding the default one.
+    pub methods: BTreeMap<String, Duration>,
+}
+
+impl Default for TimeoutConfiguration {
+    fn default() -> Self {
+        TimeoutConfiguration {
+            request: Some(Duration::from_secs(60)),
+            methods: BTreeMap::new(),
+        }
+    }
+}
+
+impl TimeoutConfiguration {
+    /// Parses `method=duration` pairs.
+    pub fn parse_methods(list: &[String]) -> Result<BTreeMap<String, Duration>, String> {
+        list.iter()
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                let mut parts = item.splitn(2, '=');
+                match (parts.next(), parts.next().and_then(|timeout| to_duration(timeout).ok())) {
+                    (Some(method), Some(timeout)) => Ok((method.to_owned(), timeout)),
+                    _ => Err(format!("Invalid method timeout given: {}. Expected method=duration.", item)),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns deadline of a request calling given methods.
+    /// Batches get the longest deadline of their calls, `None` if any of the calls is not limited.
+    /// Zero duration means no limit.
+    pub fn deadline<'a, I: IntoIterator<Item = &'a str>>(&self, methods: I) -> Option<Duration> {
+        let unlimited = Duration::from_secs(0);
+        let mut deadline = None;
+        for method in methods {
+            match self.methods.get(method).cloned().or(self.request) {
+                Some(timeout) if timeout != unlimited => deadline = cmp::max(deadline, Some(timeout)),
+                _ => return None,
+            }
+        }
+        deadline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::TimeoutConfiguration;
+
+    #[test]
+    fn should_parse_method_timeouts() {
+        // given
+        let list = vec!["parity_devLogs=5seconds".to_owned(), "web3_sha3=1".to_owned()];
+
+        // when
+        let methods = TimeoutConfiguration::parse_methods(&list).unwrap();
+
+        // then
+        assert_eq!(methods["parity_devLogs"], Duration::from_secs(5));
+        assert_eq!(methods["web3_sha3"], Duration::from_secs(1));
+        assert!(TimeoutConfiguration::parse_methods(&["web3_sha3=soon".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn should_return_longest_deadline_of_a_batch() {
+        // given
+        let mut conf = TimeoutConfiguration::default();
+        conf.methods.insert("slow".into(), Duration::from_secs(120));
+        conf.methods.insert("fast".into(), Duration::from_secs(1));
+
+        // then
+        assert_eq!(conf.deadline(vec!["fast"]), Some(Duration::from_secs(1)));
+        assert_eq!(conf.deadline(vec!["other"]), Some(Duration::from_secs(60)));
+        assert_eq!(conf.deadline(vec!["fast", "slow"]), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn should_disable_timeouts() {
+        // given
+        let mut conf = TimeoutConfiguration {
+            request: None,
+            ..Default::default()
+        };
+        conf.methods.insert("fast".into(), Duration::from_secs(1));
+        conf.methods.insert("forever".into(), Duration::from_secs(0));
+
+        // then
+        assert_eq!(conf.deadline(vec!["fast"]), Some(Duration::from_secs(1)));
+        assert_eq!(conf.deadline(vec!["fast", "other"]), None);
+        assert_eq!(conf.deadline(vec!["forever"]), None);
+    }
+}
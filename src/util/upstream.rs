@@ -0,0 +1,557 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+//! Forwarding of unknown methods to upstream JSON-RPC nodes.
+
+use std::io::Read;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use fetch::{self, Client as FetchClient, Fetch};
+use futures::{future, Future};
+use futures::future::{Either, Loop};
+use jsonrpc_core as rpc;
+use parking_lot::{Mutex, RwLock};
+use serde_json;
+use api::apis::MethodApis;
+use util::informant::{reject, merge};
+
+/// Error code returned when none of the upstream nodes could handle the request.
+pub const UPSTREAM_ERROR_CODE: i64 = -32009;
+
+/// Methods forwarded when no other methods are allowed. They only read public chain state,
+/// so they are forwarded for any caller.
+pub const DEFAULT_ALLOW: &'static [&'static str] = &["eth_blockNumber",
+                                                     "eth_call",
+                                                     "eth_chainId",
+                                                     "eth_estimateGas",
+                                                     "eth_gasPrice",
+                                                     "eth_get*",
+                                                     "eth_protocolVersion",
+                                                     "eth_syncing",
+                                                     "net_*",
+                                                     "web3_*"];
+
+/// Methods which are never forwarded, as they expose accounts, secrets or change node settings.
+pub const BUILTIN_DENY: &'static [&'static str] = &["personal_*",
+                                                    "signer_*",
+                                                    "secretstore_*",
+                                                    "shh_*",
+                                                    "parity_set*",
+                                                    "parity_*Account*",
+                                                    "parity_*Vault*",
+                                                    "parity_signMessage",
+                                                    "parity_testPassword",
+                                                    "parity_changePassword",
+                                                    "parity_generateSecretPhrase",
+                                                    "parity_phraseToAddress",
+                                                    "parity_removeAddress",
+                                                    "parity_*Reserved*",
+                                                    "parity_executeUpgrade",
+                                                    "parity_upgradeReady",
+                                                    "parity_postTransaction",
+                                                    "parity_postSign",
+                                                    "parity_decryptMessage",
+                                                    "eth_accounts",
+                                                    "eth_coinbase",
+                                                    "eth_sign*",
+                                                    "eth_sendTransaction"];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct UpstreamConfiguration {
+    /// URLs of upstream nodes in order of preference.
+    pub urls: Vec<String>,
+    /// Methods which may be forwarded (`None` allows only `DEFAULT_ALLOW`).
+    pub allow: Option<Vec<String>>,
+    /// Methods which are never forwarded in addition to `BUILTIN_DENY`.
+    pub deny: Vec<String>,
+    /// How long a failed upstream is skipped.
+    pub retry_after: Duration,
+}
+
+impl Default for UpstreamConfiguration {
+    fn default() -> Self {
+        UpstreamConfiguration {
+            urls: Vec::new(),
+            allow: None,
+            deny: Vec::new(),
+            retry_after: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Returns true if method name matches the pattern, `*` matches any sequence of characters.
+fn matches(pattern: &str, method: &str) -> bool {
+    match pattern.find('*') {
+        None => pattern == method,
+        Some(idx) => {
+            let (prefix, rest) = (&pattern[..idx], &pattern[idx + 1..]);
+            method.starts_with(prefix) &&
+            (prefix.len()..method.len() + 1).any(|start| matches(rest, &method[start..]))
+        }
+    }
+}
+
+/// Single upstream node with its health.
+struct Upstream {
+    url: String,
+    down_until: Mutex<Option<Instant>>,
+}
+
+impl Upstream {
+    fn is_healthy(&self) -> bool {
+        self.down_until.lock().map_or(true, |until| until <= Instant::now())
+    }
+
+    fn succeeded(&self) {
+        *self.down_until.lock() = None;
+    }
+
+    fn failed(&self, retry_after: Duration, error: &str) {
+        warn!(target: "rpc", "Upstream {} failed: {}", self.url, error);
+        *self.down_until.lock() = Some(Instant::now() + retry_after);
+    }
+}
+
+/// Patterns deciding which methods may be forwarded.
+struct Rules {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl Rules {
+    fn new(allow: Option<Vec<String>>, deny: Vec<String>) -> Self {
+        Rules {
+            allow: allow.unwrap_or_else(|| DEFAULT_ALLOW.iter().map(|pattern| pattern.to_string()).collect()),
+            deny: BUILTIN_DENY.iter().map(|pattern| pattern.to_string()).chain(deny).collect(),
+        }
+    }
+}
+
+/// Forwards calls of unknown methods to upstream nodes, failing over to the next one on errors.
+pub struct Upstreams {
+    upstreams: Vec<Arc<Upstream>>,
+    rules: RwLock<Rules>,
+    retry_after: Duration,
+    fetch: FetchClient,
+}
+
+impl Upstreams {
+    /// Creates new `Upstreams`, returns `None` if no upstream is configured.
+    pub fn new(conf: UpstreamConfiguration) -> Result<Option<Self>, String> {
+        if conf.urls.is_empty() {
+            return Ok(None);
+        }
+
+        let fetch = FetchClient::new().map_err(|e| format!("Unable to create upstream client: {:?}", e))?;
+        Ok(Some(Upstreams {
+            upstreams: conf.urls
+                .into_iter()
+                .map(|url| {
+                    Arc::new(Upstream {
+                        url: url,
+                        down_until: Mutex::new(None),
+                    })
+                })
+                .collect(),
+            rules: RwLock::new(Rules::new(conf.allow, conf.deny)),
+            retry_after: conf.retry_after,
+            fetch: fetch,
+        }))
+    }
+
+    /// Returns true if calls of given method may be forwarded.
+    pub fn allows(&self, method: &str) -> bool {
+        let rules = self.rules.read();
+        !rules.deny.iter().any(|pattern| matches(pattern, method)) &&
+        rules.allow.iter().any(|pattern| matches(pattern, method))
+    }
+
+    /// Returns true if calls of given method are forwarded only for authenticated callers.
+    /// Methods outside of `DEFAULT_ALLOW` may expose state of the upstream node.
+    pub fn requires_auth(&self, method: &str) -> bool {
+        !DEFAULT_ALLOW.iter().any(|pattern| matches(pattern, method))
+    }
+
+    /// Replaces methods which may be forwarded.
+    pub fn set_rules(&self, allow: Option<Vec<String>>, deny: Vec<String>) {
+        *self.rules.write() = Rules::new(allow, deny);
+    }
+
+    /// Returns URLs of upstream nodes in order of preference.
+    pub fn urls(&self) -> Vec<String> {
+        self.upstreams.iter().map(|upstream| upstream.url.clone()).collect()
+    }
+
+    /// Returns URLs of upstream nodes with their health.
+    pub fn status(&self) -> Vec<(String, bool)> {
+        self.upstreams.iter().map(|upstream| (upstream.url.clone(), upstream.is_healthy())).collect()
+    }
+
+    /// Processes calls of known methods locally and forwards the remaining ones.
+    pub fn route<F, X>(&self, request: rpc::Request, known: &MethodApis, process: F) -> rpc::FutureResponse
+        where F: FnOnce(rpc::Request) -> X,
+              X: Future<Item = Option<rpc::Response>, Error = ()> + Send + 'static
+    {
+        let forwarded = |call: &rpc::Call| {
+            let method = match *call {
+                rpc::Call::MethodCall(ref call) => &call.method,
+                rpc::Call::Notification(ref notification) => &notification.method,
+                rpc::Call::Invalid(_) => return false,
+            };
+            !known.contains_key(method) && self.allows(method)
+        };
+
+        match request {
+            rpc::Request::Single(call) => {
+                match forwarded(&call) {
+                    true => self.forward(rpc::Request::Single(call)),
+                    false => process(rpc::Request::Single(call)).boxed(),
+                }
+            }
+            rpc::Request::Batch(calls) => {
+                let (remote, local): (Vec<_>, Vec<_>) = calls.into_iter().partition(|call| forwarded(call));
+                match (local.is_empty(), remote.is_empty()) {
+                    (_, true) => process(rpc::Request::Batch(local)).boxed(),
+                    (true, false) => self.forward(rpc::Request::Batch(remote)),
+                    (false, false) => {
+                        process(rpc::Request::Batch(local))
+                            .join(self.forward(rpc::Request::Batch(remote)))
+                            .map(|(local, remote)| merge(local, remote))
+                            .boxed()
+                    }
+                }
+            }
+        }
+    }
+
+    /// Forwards the request to the first healthy upstream, trying the other ones on failure.
+    fn forward(&self, request: rpc::Request) -> rpc::FutureResponse {
+        let body = match serde_json::to_vec(&request) {
+            Ok(body) => body,
+            Err(e) => return future::ok(reject(&request, upstream_error(&e.to_string()))).boxed(),
+        };
+
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) = self.upstreams
+            .iter()
+            .cloned()
+            .partition(|upstream| upstream.is_healthy());
+        let candidates = healthy.into_iter().chain(unhealthy).collect::<Vec<_>>().into_iter();
+
+        let fetch = self.fetch.clone();
+        let retry_after = self.retry_after;
+        let attempts = future::loop_fn((candidates, None), move |(mut candidates, last_error)| {
+            let upstream = match candidates.next() {
+                Some(upstream) => upstream,
+                None => return Either::A(future::ok::<_, ()>(Loop::Break(Err(last_error.unwrap_or_default())))),
+            };
+
+            Either::B(fetch.post_json(&upstream.url, body.clone()).then(move |response| {
+                match read_response(response) {
+                    Ok(response) => {
+                        upstream.succeeded();
+                        Ok(Loop::Break(Ok(response)))
+                    }
+                    Err(error) => {
+                        upstream.failed(retry_after, &error);
+                        Ok(Loop::Continue((candidates, Some(error))))
+                    }
+                }
+            }))
+        });
+
+        self.fetch
+            .process(attempts)
+            .map(move |result| match result {
+                Ok(response) => response,
+                Err(error) => reject(&request, upstream_error(&error)),
+            })
+            .boxed()
+    }
+}
+
+fn upstream_error(error: &str) -> rpc::Error {
+    rpc::Error {
+        code: rpc::ErrorCode::ServerError(UPSTREAM_ERROR_CODE),
+        message: "Upstream node unavailable.".into(),
+        data: Some(rpc::Value::String(error.into())),
+    }
+}
+
+/// Reads JSON-RPC response returned by the upstream (`None` if the request contained only notifications).
+fn read_response(response: Result<fetch::Response, fetch::Error>) -> Result<Option<rpc::Response>, String> {
+    let mut response = response.map_err(|e| format!("{:?}", e))?;
+    if !response.is_success() {
+        return Err(format!("Unexpected status: {}", response.status()));
+    }
+
+    let mut body = String::new();
+    response.read_to_string(&mut body).map_err(|e| e.to_string())?;
+    match body.trim().is_empty() {
+        true => Ok(None),
+        false => serde_json::from_str(&body).map(Some).map_err(|e| format!("Invalid response: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::sync::Arc;
+    use std::thread;
+    use jsonrpc_core::{MetaIoHandler, Value};
+    use api::apis::{Api, MethodApis};
+    use types::{Metadata, Origin, H256};
+    use util::informant::{RpcStats, ClientNotifier, Middleware};
+    use super::{matches, Upstreams, UpstreamConfiguration};
+
+    /// Serves given number of requests, always replying with `response`.
+    fn stub_upstream(requests: usize, response: &'static str) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || for stream in listener.incoming().take(requests) {
+            let mut stream = stream.unwrap();
+            let mut length = 0;
+            {
+                let mut reader = BufReader::new(&stream);
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line.trim().is_empty() {
+                        break;
+                    }
+                    let lower = line.to_lowercase();
+                    if lower.starts_with("content-length:") {
+                        length = lower[15..].trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).unwrap();
+            }
+            write!(stream,
+                   "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   response.len(),
+                   response)
+                .unwrap();
+        });
+        addr
+    }
+
+    fn closed_port() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+    }
+
+    fn io(urls: Vec<String>, allow: Option<Vec<String>>) -> (MetaIoHandler<Metadata, Middleware>, Arc<Upstreams>) {
+        let conf = UpstreamConfiguration {
+            urls: urls,
+            allow: allow,
+            ..Default::default()
+        };
+        let upstreams = Arc::new(Upstreams::new(conf).unwrap().unwrap());
+        let mut methods = MethodApis::new();
+        methods.insert("web3_clientVersion".into(), Api::Web3);
+        let middleware = Middleware::new(Arc::new(RpcStats::default()), ClientNotifier::default(), None)
+            .with_method_apis(methods)
+            .with_upstreams(Some(upstreams.clone()));
+        let mut io = MetaIoHandler::with_middleware(middleware);
+        io.add_method("web3_clientVersion", |_| Ok(Value::String("toyrpc".into())));
+        (io, upstreams)
+    }
+
+    #[test]
+    fn should_match_method_patterns() {
+        assert!(matches("eth_sendTransaction", "eth_sendTransaction"));
+        assert!(!matches("eth_sendTransaction", "eth_sendRawTransaction"));
+        assert!(matches("personal_*", "personal_unlockAccount"));
+        assert!(matches("parity_*Account*", "parity_killAccount"));
+        assert!(!matches("parity_*Account*", "parity_chain"));
+        assert!(matches("*", "eth_blockNumber"));
+    }
+
+    #[test]
+    fn should_allow_methods_which_are_not_denied() {
+        // given
+        let conf = UpstreamConfiguration {
+            urls: vec!["http://127.0.0.1:8545".into()],
+            allow: Some(vec!["eth_*".into()]),
+            ..Default::default()
+        };
+
+        // when
+        let upstreams = Upstreams::new(conf).unwrap().unwrap();
+
+        // then
+        assert!(upstreams.allows("eth_blockNumber"));
+        assert!(!upstreams.allows("eth_signTransaction"));
+        assert!(!upstreams.allows("net_version"));
+        assert!(Upstreams::new(UpstreamConfiguration::default()).unwrap().is_none());
+    }
+
+    #[test]
+    fn should_allow_only_public_reads_by_default() {
+        // given
+        let conf = UpstreamConfiguration {
+            urls: vec!["http://127.0.0.1:8545".into()],
+            ..Default::default()
+        };
+
+        // when
+        let upstreams = Upstreams::new(conf).unwrap().unwrap();
+
+        // then
+        assert!(upstreams.allows("eth_blockNumber"));
+        assert!(upstreams.allows("eth_getBalance"));
+        assert!(upstreams.allows("net_version"));
+        assert!(!upstreams.allows("eth_sendRawTransaction"));
+        assert!(!upstreams.allows("parity_chain"));
+        assert!(!upstreams.requires_auth("eth_getBalance"));
+        assert!(upstreams.requires_auth("parity_chain"));
+    }
+
+    #[test]
+    fn should_always_deny_builtin_patterns() {
+        // given
+        let conf = UpstreamConfiguration {
+            urls: vec!["http://127.0.0.1:8545".into()],
+            allow: Some(vec!["*".into()]),
+            deny: vec!["eth_getWork".into()],
+            ..Default::default()
+        };
+
+        // when
+        let upstreams = Upstreams::new(conf).unwrap().unwrap();
+
+        // then
+        assert!(!upstreams.allows("eth_getWork"));
+        assert!(!upstreams.allows("personal_unlockAccount"));
+        assert!(!upstreams.allows("parity_postTransaction"));
+        assert!(!upstreams.allows("parity_postSign"));
+        assert!(!upstreams.allows("parity_decryptMessage"));
+        assert!(!upstreams.allows("parity_executeUpgrade"));
+        assert!(!upstreams.allows("parity_upgradeReady"));
+        assert!(!upstreams.allows("parity_addReservedPeer"));
+        assert!(!upstreams.allows("parity_dropNonReservedPeers"));
+        assert!(!upstreams.allows("secretstore_encrypt"));
+        assert!(!upstreams.allows("shh_post"));
+        assert!(upstreams.allows("eth_blockNumber"));
+        assert!(upstreams.allows("parity_chain"));
+    }
+
+    #[test]
+    fn should_deny_account_and_secret_methods_despite_broad_allow_rules() {
+        // given
+        let conf = UpstreamConfiguration {
+            urls: vec!["http://127.0.0.1:8545".into()],
+            allow: Some(vec!["eth_*".into(), "parity_*".into()]),
+            ..Default::default()
+        };
+
+        // when
+        let upstreams = Upstreams::new(conf).unwrap().unwrap();
+
+        // then
+        for method in &["eth_accounts",
+                        "eth_coinbase",
+                        "parity_signMessage",
+                        "parity_testPassword",
+                        "parity_changePassword",
+                        "parity_newVault",
+                        "parity_openVault",
+                        "parity_listVaults",
+                        "parity_setVaultMeta",
+                        "parity_generateSecretPhrase",
+                        "parity_phraseToAddress",
+                        "parity_removeAddress",
+                        "parity_allAccountsInfo"] {
+            assert!(!upstreams.allows(method), "{} should be denied", method);
+        }
+        assert!(upstreams.allows("eth_getBalance"));
+        assert!(upstreams.allows("parity_chain"));
+    }
+
+    #[test]
+    fn should_forward_only_for_authenticated_callers() {
+        // given
+        let up = stub_upstream(3, r#"{"jsonrpc":"2.0","result":"0x10","id":1}"#);
+        let (io, _) = io(vec![format!("http://{}", up)], Some(vec!["*".into()]));
+        let restricted = Metadata {
+            apis: Some(Arc::new(vec![Api::Web3].into_iter().collect())),
+            ..Default::default()
+        };
+        let request = r#"{"jsonrpc":"2.0","method":"parity_chain","params":[],"id":1}"#;
+
+        // when
+        let unrestricted = io.handle_request_sync(request, Metadata::default()).unwrap();
+        let rejected = io.handle_request_sync(request, restricted.clone()).unwrap();
+        let local = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"web3_clientVersion","params":[],"id":2}"#,
+                                           restricted.clone());
+        let public = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"eth_blockNumber","params":[],"id":1}"#,
+                                            restricted.clone());
+        let forwarded = io.handle_request_sync(request, Metadata { authenticated: true, ..restricted });
+        let ipc = io.handle_request_sync(request, Metadata { origin: Origin::Ipc(H256::from(1u64)), ..Default::default() });
+
+        // then
+        assert!(unrestricted.contains(r#""code":-32006"#), "{}", unrestricted);
+        assert!(rejected.contains(r#""code":-32006"#), "{}", rejected);
+        assert_eq!(local, Some(r#"{"jsonrpc":"2.0","result":"toyrpc","id":2}"#.into()));
+        assert_eq!(public, Some(r#"{"jsonrpc":"2.0","result":"0x10","id":1}"#.into()));
+        assert_eq!(forwarded, Some(r#"{"jsonrpc":"2.0","result":"0x10","id":1}"#.into()));
+        assert_eq!(ipc, Some(r#"{"jsonrpc":"2.0","result":"0x10","id":1}"#.into()));
+    }
+
+    #[test]
+    fn should_forward_unknown_methods_with_failover() {
+        // given
+        let down = closed_port();
+        let up = stub_upstream(1, r#"{"jsonrpc":"2.0","result":"0x10","id":1}"#);
+        let (io, upstreams) = io(vec![format!("http://{}", down), format!("http://{}", up)], None);
+
+        // when
+        let forwarded = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"eth_blockNumber","params":[],"id":1}"#,
+                                               Metadata::default());
+        let local = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"web3_clientVersion","params":[],"id":2}"#,
+                                           Metadata::default());
+
+        // then
+        assert_eq!(forwarded, Some(r#"{"jsonrpc":"2.0","result":"0x10","id":1}"#.into()));
+        assert_eq!(local, Some(r#"{"jsonrpc":"2.0","result":"toyrpc","id":2}"#.into()));
+        assert_eq!(upstreams.status(),
+                   vec![(format!("http://{}", down), false), (format!("http://{}", up), true)]);
+    }
+
+    #[test]
+    fn should_not_forward_denied_methods() {
+        // given
+        let (io, _) = io(vec![format!("http://{}", closed_port())], None);
+
+        // when
+        let res = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"personal_unlockAccount","params":[],"id":1}"#,
+                                         Metadata::default());
+
+        // then
+        assert_eq!(res, Some(r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":1}"#.into()));
+    }
+
+    #[test]
+    fn should_report_unavailable_upstreams() {
+        // given
+        let (io, _) = io(vec![format!("http://{}", closed_port())], None);
+
+        // when
+        let res = io.handle_request_sync(r#"{"jsonrpc":"2.0","method":"eth_blockNumber","params":[],"id":1}"#,
+                                         Metadata::default()).unwrap();
+
+        // then
+        assert!(res.starts_with(r#"{"jsonrpc":"2.0","error":{"code":-32009,"message":"Upstream node unavailable.""#));
+    }
+}